@@ -0,0 +1,383 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::constants::gamepad::STICK_DEADZONE;
+use crate::constants::save::{AUTOSAVE_INTERVAL, SAVE_DIR, SETTINGS_FILE, SETTINGS_VERSION};
+use crate::save::{write_ron, SaveError};
+
+// everything the player can do, the systems ask for actions instead of keys
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum Action {
+    MoveLeft,
+    MoveRight,
+    Jump,
+    // mines with a pickaxe, places blocks and stations
+    UseItem,
+    // places blocks and stations too
+    Interact,
+    OpenInventory,
+    QuickSave,
+    // cycle through the hotbar like the mouse wheel
+    NextSlot,
+    PreviousSlot,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum AxisSide {
+    Negative,
+    Positive,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Binding {
+    Key(KeyCode),
+    Mouse(MouseButton),
+    // pressed on any of the connected gamepads
+    Gamepad(GamepadButtonType),
+    // a stick tilted to one side, it can hold an action but is never just pressed
+    Axis(GamepadAxisType, AxisSide),
+}
+
+// read from SETTINGS_FILE, actions missing in the file keep their default bindings
+#[derive(Resource, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct InputSettings {
+    // files from before the versioning have no version at all
+    #[serde(default = "first_settings_version")]
+    pub version: u32,
+    pub bindings: BTreeMap<Action, Vec<Binding>>,
+    // seconds between two autosaves, 0 turns the autosave off
+    #[serde(default = "default_autosave_interval")]
+    pub autosave_interval: f32,
+}
+
+fn default_autosave_interval() -> f32 {
+    AUTOSAVE_INTERVAL
+}
+
+fn first_settings_version() -> u32 {
+    1
+}
+
+// default bindings of actions which existed already, with the version of the file they came in;
+// older files get them too, unless another action took the input meanwhile
+const ADDED_BINDINGS: [(u32, Action, Binding); 2] = [
+    (
+        2,
+        Action::MoveLeft,
+        Binding::Axis(GamepadAxisType::LeftStickX, AxisSide::Negative),
+    ),
+    (
+        2,
+        Action::MoveRight,
+        Binding::Axis(GamepadAxisType::LeftStickX, AxisSide::Positive),
+    ),
+];
+
+// state of the bound inputs, read through the actions
+#[derive(SystemParam)]
+pub struct Actions<'w, 's> {
+    settings: Res<'w, InputSettings>,
+    keys: Res<'w, Input<KeyCode>>,
+    mouse: Res<'w, Input<MouseButton>>,
+    gamepad_buttons: Res<'w, Input<GamepadButton>>,
+    gamepads: Res<'w, Gamepads>,
+    axes: Res<'w, Axis<GamepadAxis>>,
+    #[system_param(ignore)]
+    marker: PhantomData<&'s ()>,
+}
+
+impl Action {
+    pub const ALL: [Action; 9] = [
+        Action::MoveLeft,
+        Action::MoveRight,
+        Action::Jump,
+        Action::UseItem,
+        Action::Interact,
+        Action::OpenInventory,
+        Action::QuickSave,
+        Action::NextSlot,
+        Action::PreviousSlot,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Action::MoveLeft => "Move left",
+            Action::MoveRight => "Move right",
+            Action::Jump => "Jump",
+            Action::UseItem => "Use item",
+            Action::Interact => "Interact",
+            Action::OpenInventory => "Open inventory",
+            Action::QuickSave => "Quick save",
+            Action::NextSlot => "Next slot",
+            Action::PreviousSlot => "Previous slot",
+        }
+    }
+
+    fn default_bindings(self) -> Vec<Binding> {
+        match self {
+            Action::MoveLeft => vec![
+                Binding::Key(KeyCode::A),
+                Binding::Gamepad(GamepadButtonType::DPadLeft),
+                Binding::Axis(GamepadAxisType::LeftStickX, AxisSide::Negative),
+            ],
+            Action::MoveRight => vec![
+                Binding::Key(KeyCode::D),
+                Binding::Gamepad(GamepadButtonType::DPadRight),
+                Binding::Axis(GamepadAxisType::LeftStickX, AxisSide::Positive),
+            ],
+            Action::Jump => vec![
+                Binding::Key(KeyCode::Space),
+                Binding::Gamepad(GamepadButtonType::South),
+            ],
+            Action::UseItem => vec![
+                Binding::Mouse(MouseButton::Left),
+                Binding::Gamepad(GamepadButtonType::RightTrigger2),
+            ],
+            Action::Interact => vec![
+                Binding::Mouse(MouseButton::Right),
+                Binding::Gamepad(GamepadButtonType::LeftTrigger2),
+            ],
+            Action::OpenInventory => vec![
+                Binding::Key(KeyCode::C),
+                Binding::Gamepad(GamepadButtonType::North),
+            ],
+            Action::QuickSave => vec![Binding::Key(KeyCode::F5)],
+            Action::NextSlot => vec![Binding::Gamepad(GamepadButtonType::RightTrigger)],
+            Action::PreviousSlot => vec![Binding::Gamepad(GamepadButtonType::LeftTrigger)],
+        }
+    }
+}
+
+impl Binding {
+    pub fn name(&self) -> String {
+        match self {
+            Binding::Key(key) => format!("{key:?}"),
+            Binding::Mouse(button) => format!("Mouse {button:?}"),
+            Binding::Gamepad(button) => format!("Pad {button:?}"),
+            Binding::Axis(axis, AxisSide::Negative) => format!("Pad {axis:?} -"),
+            Binding::Axis(axis, AxisSide::Positive) => format!("Pad {axis:?} +"),
+        }
+    }
+}
+
+impl Default for InputSettings {
+    fn default() -> Self {
+        Self {
+            version: SETTINGS_VERSION,
+            bindings: Action::ALL
+                .iter()
+                .map(|action| (*action, action.default_bindings()))
+                .collect(),
+            autosave_interval: default_autosave_interval(),
+        }
+    }
+}
+
+impl InputSettings {
+    pub fn bindings(&self, action: Action) -> &[Binding] {
+        self.bindings
+            .get(&action)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    // index None adds a new binding to the action
+    pub fn rebind(&mut self, action: Action, index: Option<usize>, binding: Binding) {
+        let bindings = self.bindings.entry(action).or_default();
+        match index.filter(|idx| *idx < bindings.len()) {
+            Some(idx) => bindings[idx] = binding,
+            None => bindings.push(binding),
+        }
+    }
+
+    pub fn unbind(&mut self, action: Action, index: usize) {
+        let bindings = self.bindings.entry(action).or_default();
+        if index < bindings.len() {
+            bindings.remove(index);
+        }
+    }
+
+    // other actions sharing the binding, they would all fire together
+    pub fn conflicts(&self, action: Action, binding: &Binding) -> Vec<Action> {
+        self.bindings
+            .iter()
+            .filter(|(other, bindings)| **other != action && bindings.contains(binding))
+            .map(|(other, _)| *other)
+            .collect()
+    }
+
+    pub fn has_conflicts(&self) -> bool {
+        self.bindings.iter().any(|(action, bindings)| {
+            bindings
+                .iter()
+                .any(|binding| !self.conflicts(*action, binding).is_empty())
+        })
+    }
+}
+
+impl Actions<'_, '_> {
+    fn binding_pressed(&self, binding: &Binding) -> bool {
+        match binding {
+            Binding::Key(key) => self.keys.pressed(*key),
+            Binding::Mouse(button) => self.mouse.pressed(*button),
+            Binding::Gamepad(button) => self.gamepads.iter().any(|gamepad| {
+                self.gamepad_buttons
+                    .pressed(GamepadButton::new(gamepad, *button))
+            }),
+            Binding::Axis(axis, side) => self.gamepads.iter().any(|gamepad| {
+                let value = self
+                    .axes
+                    .get(GamepadAxis::new(gamepad, *axis))
+                    .unwrap_or(0.);
+                match side {
+                    AxisSide::Negative => value < -STICK_DEADZONE,
+                    AxisSide::Positive => value > STICK_DEADZONE,
+                }
+            }),
+        }
+    }
+
+    fn binding_just_pressed(&self, binding: &Binding) -> bool {
+        match binding {
+            Binding::Key(key) => self.keys.just_pressed(*key),
+            Binding::Mouse(button) => self.mouse.just_pressed(*button),
+            Binding::Gamepad(button) => self.gamepads.iter().any(|gamepad| {
+                self.gamepad_buttons
+                    .just_pressed(GamepadButton::new(gamepad, *button))
+            }),
+            Binding::Axis(..) => false,
+        }
+    }
+
+    pub fn pressed(&self, action: Action) -> bool {
+        self.settings
+            .bindings(action)
+            .iter()
+            .any(|binding| self.binding_pressed(binding))
+    }
+
+    pub fn just_pressed(&self, action: Action) -> bool {
+        self.settings
+            .bindings(action)
+            .iter()
+            .any(|binding| self.binding_just_pressed(binding))
+    }
+}
+
+pub fn settings_path() -> PathBuf {
+    Path::new(SAVE_DIR).join(SETTINGS_FILE)
+}
+
+fn parse_input_settings(data: &str) -> Result<InputSettings, SaveError> {
+    let mut settings: InputSettings = ron::from_str(data)?;
+    for (version, action, binding) in ADDED_BINDINGS {
+        let taken = !settings.conflicts(action, &binding).is_empty();
+        if version <= settings.version || taken {
+            continue;
+        }
+        if let Some(bindings) = settings.bindings.get_mut(&action) {
+            if !bindings.contains(&binding) {
+                bindings.push(binding);
+            }
+        }
+    }
+    // actions added after the file was written
+    for action in Action::ALL {
+        settings
+            .bindings
+            .entry(action)
+            .or_insert_with(|| action.default_bindings());
+    }
+    settings.version = SETTINGS_VERSION;
+    Ok(settings)
+}
+
+fn read_input_settings(path: &Path) -> Result<InputSettings, SaveError> {
+    parse_input_settings(&fs::read_to_string(path)?)
+}
+
+pub fn save_input_settings(settings: &InputSettings) -> Result<(), SaveError> {
+    write_ron(&settings_path(), settings)
+}
+
+pub fn load_input_settings(mut commands: Commands) {
+    let settings = if settings_path().exists() {
+        match read_input_settings(&settings_path()) {
+            Ok(settings) => settings,
+            Err(err) => {
+                warn!("cannot read the input settings, using the default ones: {err}");
+                InputSettings::default()
+            }
+        }
+    } else {
+        InputSettings::default()
+    };
+    if settings.has_conflicts() {
+        warn!("some inputs are bound to more than one action");
+    }
+    commands.insert_resource(settings);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn old_settings_get_new_default_bindings() {
+        let settings = parse_input_settings(
+            r#"(
+                bindings: {
+                    MoveLeft: [Key(A)],
+                    MoveRight: [Key(D)],
+                    Jump: [],
+                },
+            )"#,
+        )
+        .unwrap();
+        assert_eq!(settings.version, SETTINGS_VERSION);
+        assert_eq!(
+            settings.bindings(Action::MoveLeft),
+            [
+                Binding::Key(KeyCode::A),
+                Binding::Axis(GamepadAxisType::LeftStickX, AxisSide::Negative)
+            ]
+        );
+        // an action the player unbound stays unbound, missing ones get their defaults
+        assert!(settings.bindings(Action::Jump).is_empty());
+        assert_eq!(
+            settings.bindings(Action::QuickSave),
+            Action::QuickSave.default_bindings()
+        );
+    }
+
+    #[test]
+    fn current_settings_are_left_alone() {
+        let mut settings = InputSettings::default();
+        settings.unbind(Action::MoveLeft, 2);
+        let data = ron::to_string(&settings).unwrap();
+        assert_eq!(parse_input_settings(&data).unwrap(), settings);
+    }
+
+    #[test]
+    fn taken_inputs_are_not_added() {
+        let settings = parse_input_settings(
+            r#"(
+                bindings: {
+                    MoveLeft: [Key(A)],
+                    Jump: [Axis(LeftStickX, Negative)],
+                },
+            )"#,
+        )
+        .unwrap();
+        assert_eq!(
+            settings.bindings(Action::MoveLeft),
+            [Binding::Key(KeyCode::A)]
+        );
+    }
+}
@@ -0,0 +1,58 @@
+use std::f32::consts::TAU;
+
+use bevy::prelude::*;
+use serde::Deserialize;
+
+use crate::constants::world::{DAY_LENGTH, START_TIME_OF_DAY};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+pub enum TimeOfDay {
+    Day,
+    Night,
+}
+
+// time in the world as the share of the day passed since midnight
+#[derive(Resource)]
+pub struct WorldClock {
+    day_share: f32,
+}
+
+impl Default for WorldClock {
+    fn default() -> Self {
+        Self::at(START_TIME_OF_DAY)
+    }
+}
+
+impl WorldClock {
+    pub fn at(day_share: f32) -> Self {
+        Self {
+            day_share: day_share.rem_euclid(1.),
+        }
+    }
+
+    pub fn advance(&mut self, seconds: f32) {
+        self.day_share = (self.day_share + seconds / DAY_LENGTH).rem_euclid(1.);
+    }
+
+    // brightness of the sky, 0 at midnight and 1 at noon
+    pub fn daylight(&self) -> f32 {
+        (1. - (self.day_share * TAU).cos()) / 2.
+    }
+
+    pub fn time_of_day(&self) -> TimeOfDay {
+        if self.daylight() >= 0.5 {
+            TimeOfDay::Day
+        } else {
+            TimeOfDay::Night
+        }
+    }
+}
+
+// every visit to a world starts in the morning
+pub fn reset_world_clock(mut clock: ResMut<WorldClock>) {
+    *clock = WorldClock::default();
+}
+
+pub fn advance_world_clock(time: Res<Time>, mut clock: ResMut<WorldClock>) {
+    clock.advance(time.delta_seconds());
+}
@@ -0,0 +1,209 @@
+use std::collections::HashSet;
+
+use bevy::prelude::*;
+use bevy_ecs_tilemap::prelude::*;
+use bevy_rapier2d::prelude::*;
+
+use crate::constants::{collision_groups::MAP_COLLIDE_WITH_ALL_EXCEPT_MAP, map::*};
+use crate::game_state::WorldEntity;
+use crate::map::{TileChanged, WithColliders};
+
+// one merged collider, it always covers tiles of a single chunk
+#[derive(Component)]
+pub struct MapCollider {
+    chunk: UVec2,
+}
+
+// rectangle of tiles, both corners are inclusive
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct TileRect {
+    min: UVec2,
+    max: UVec2,
+}
+
+fn chunk_count(map_size: &TilemapSize) -> UVec2 {
+    UVec2::new(
+        (map_size.x + COLLIDER_CHUNK_SIZE - 1) / COLLIDER_CHUNK_SIZE,
+        (map_size.y + COLLIDER_CHUNK_SIZE - 1) / COLLIDER_CHUNK_SIZE,
+    )
+}
+
+fn chunk_of(x: u32, y: u32) -> UVec2 {
+    UVec2::new(x / COLLIDER_CHUNK_SIZE, y / COLLIDER_CHUNK_SIZE)
+}
+
+// everything outside of the map counts as solid,
+// so tiles on the map border do not get colliders only because of it
+fn is_solid(tile_storage: &TileStorage, x: i64, y: i64) -> bool {
+    let size = tile_storage.size;
+    if x < 0 || y < 0 || x >= size.x as i64 || y >= size.y as i64 {
+        return true;
+    }
+    tile_storage
+        .get(&TilePos {
+            x: x as u32,
+            y: y as u32,
+        })
+        .is_some()
+}
+
+// only solid tiles touching the air can be touched by anything else
+fn needs_collider(tile_storage: &TileStorage, x: u32, y: u32) -> bool {
+    let (x, y) = (x as i64, y as i64);
+    is_solid(tile_storage, x, y)
+        && [(-1, 0), (1, 0), (0, -1), (0, 1)]
+            .iter()
+            .any(|(dx, dy)| !is_solid(tile_storage, x + dx, y + dy))
+}
+
+// greedily covers all marked cells with rectangles:
+// first the longest possible row is taken, then it is stretched upwards as long as the whole row fits
+fn merge_into_rectangles(
+    width: u32,
+    height: u32,
+    marked: impl Fn(u32, u32) -> bool,
+) -> Vec<TileRect> {
+    let (w, h) = (width as usize, height as usize);
+    let mut todo = vec![vec![false; h]; w];
+    for (x, column) in todo.iter_mut().enumerate() {
+        for (y, cell) in column.iter_mut().enumerate() {
+            *cell = marked(x as u32, y as u32);
+        }
+    }
+
+    let mut rects = Vec::new();
+    for y in 0..h {
+        for x in 0..w {
+            if !todo[x][y] {
+                continue;
+            }
+
+            let mut end_x = x;
+            while end_x + 1 < w && todo[end_x + 1][y] {
+                end_x += 1;
+            }
+
+            let mut end_y = y;
+            while end_y + 1 < h && (x..=end_x).all(|ix| todo[ix][end_y + 1]) {
+                end_y += 1;
+            }
+
+            for column in todo.iter_mut().take(end_x + 1).skip(x) {
+                for cell in column.iter_mut().take(end_y + 1).skip(y) {
+                    *cell = false;
+                }
+            }
+
+            rects.push(TileRect {
+                min: UVec2::new(x as u32, y as u32),
+                max: UVec2::new(end_x as u32, end_y as u32),
+            });
+        }
+    }
+    rects
+}
+
+fn spawn_rect_collider(
+    commands: &mut Commands,
+    map_size: &TilemapSize,
+    chunk: UVec2,
+    rect: TileRect,
+) {
+    let tiles = (rect.max - rect.min + UVec2::ONE).as_vec2();
+    let center = (rect.min.as_vec2() + rect.max.as_vec2()) / 2.
+        * Vec2::new(GRID_SIZE.x, GRID_SIZE.y)
+        + map_transform_vec2(map_size);
+
+    commands
+        .spawn(MapCollider { chunk })
+        .insert(WorldEntity)
+        .insert(Name::new(format!(
+            "MapCollider({},{})-({},{})",
+            rect.min.x, rect.min.y, rect.max.x, rect.max.y
+        )))
+        .insert(RigidBody::Fixed)
+        .insert(Collider::cuboid(
+            tiles.x * COLLIDER_SIZE.x,
+            tiles.y * COLLIDER_SIZE.y,
+        ))
+        .insert(MAP_COLLIDE_WITH_ALL_EXCEPT_MAP)
+        .insert(ActiveEvents::COLLISION_EVENTS)
+        .insert(TransformBundle::from(Transform::from_translation(
+            center.extend(0.),
+        )));
+}
+
+fn spawn_chunk_colliders(commands: &mut Commands, tile_storage: &TileStorage, chunk: UVec2) {
+    let origin = chunk * COLLIDER_CHUNK_SIZE;
+    let map_size = tile_storage.size;
+    let width = COLLIDER_CHUNK_SIZE.min(map_size.x - origin.x);
+    let height = COLLIDER_CHUNK_SIZE.min(map_size.y - origin.y);
+
+    let rects = merge_into_rectangles(width, height, |x, y| {
+        needs_collider(tile_storage, origin.x + x, origin.y + y)
+    });
+    for rect in rects {
+        spawn_rect_collider(
+            commands,
+            &map_size,
+            chunk,
+            TileRect {
+                min: rect.min + origin,
+                max: rect.max + origin,
+            },
+        );
+    }
+}
+
+// runs the frame after the foreground map is spawned, once its tiles are in place
+pub fn spawn_colliders(
+    mut commands: Commands,
+    tilemap_q: Query<&TileStorage, (With<WithColliders>, Added<TileStorage>)>,
+) {
+    for tile_storage in tilemap_q.iter() {
+        let chunks = chunk_count(&tile_storage.size);
+        for x in 0..chunks.x {
+            for y in 0..chunks.y {
+                spawn_chunk_colliders(&mut commands, tile_storage, UVec2::new(x, y));
+            }
+        }
+    }
+}
+
+// rebuilds colliders of every chunk touched by a mined or placed tile
+pub fn rebuild_changed_colliders(
+    mut commands: Commands,
+    mut tile_changes: EventReader<TileChanged>,
+    tilemap_q: Query<&TileStorage, With<WithColliders>>,
+    collider_q: Query<(Entity, &MapCollider)>,
+) {
+    let Ok(tile_storage) = tilemap_q.get_single() else {
+        return;
+    };
+    let map_size = tile_storage.size;
+    let mut dirty_chunks = HashSet::new();
+    for TileChanged(tile_pos) in tile_changes.iter() {
+        // a changed tile can expose or bury its neighbours, which may lay in another chunk
+        for (dx, dy) in [(0, 0), (-1, 0), (1, 0), (0, -1), (0, 1)] {
+            let x = tile_pos.x as i64 + dx;
+            let y = tile_pos.y as i64 + dy;
+            if x >= 0 && y >= 0 && x < map_size.x as i64 && y < map_size.y as i64 {
+                dirty_chunks.insert(chunk_of(x as u32, y as u32));
+            }
+        }
+    }
+
+    if dirty_chunks.is_empty() {
+        return;
+    }
+
+    for (collider_entity, collider) in collider_q.iter() {
+        if dirty_chunks.contains(&collider.chunk) {
+            commands.entity(collider_entity).despawn_recursive();
+        }
+    }
+
+    for chunk in dirty_chunks.iter() {
+        spawn_chunk_colliders(&mut commands, tile_storage, *chunk);
+    }
+}
@@ -3,8 +3,17 @@ pub mod depth {
     pub const Z_BACKGROUND: f32 = 0.;
     pub const Z_WALLS: f32 = 0.01;
     pub const Z_FOREGROUND: f32 = 0.02;
+    pub const Z_STATIONS: f32 = 0.021;
+    pub const Z_DAMAGE: f32 = 0.025;
+    pub const Z_ITEMS: f32 = 0.028;
+    pub const Z_ENEMIES: f32 = 0.029;
     pub const Z_PLAYER: f32 = 0.03;
+    pub const Z_SWING: f32 = 0.031;
+    pub const Z_PROJECTILES: f32 = 0.032;
+    pub const Z_MINING_PROGRESS: f32 = 0.035;
     pub const Z_COVER: f32 = 0.04;
+    pub const Z_DAMAGE_NUMBERS: f32 = 0.045;
+    pub const Z_GAMEPAD_CURSOR: f32 = 0.05;
 }
 
 // tile offsets for tile_strip.png texture asset
@@ -13,6 +22,8 @@ pub mod offsets {
     pub const STONE_OFFSET: u32 = 1;
     pub const WATER_OFFSET: u32 = 2;
     pub const DIAMOND_OFFSET: u32 = 3;
+    // frame of walls_strip.png
+    pub const DIRT_WALL_INDEX: u32 = 3;
 }
 
 // group collisions constants
@@ -21,6 +32,10 @@ pub mod collision_groups {
 
     const MAP_GROUP: Group = Group::from_bits_truncate(0b0001); // membership group [0]
     const PLAYER_GROUP: Group = Group::from_bits_truncate(0b0010); // membership group [1]
+    const ITEM_GROUP: Group = Group::from_bits_truncate(0b0100); // membership group [2]
+    const ENEMY_GROUP: Group = Group::from_bits_truncate(0b1000); // membership group [3]
+    const WEAPON_GROUP: Group = Group::from_bits_truncate(0b10000); // membership group [4]
+    const PROJECTILE_GROUP: Group = Group::from_bits_truncate(0b100000); // membership group [5]
 
     pub const MAP_COLLIDE_WITH_ALL_EXCEPT_MAP: CollisionGroups = CollisionGroups {
         memberships: MAP_GROUP,
@@ -31,17 +46,270 @@ pub mod collision_groups {
         memberships: PLAYER_GROUP,
         filters: Group::ALL,
     };
+
+    pub const ITEM_COLLIDE_WITH_MAP: CollisionGroups = CollisionGroups {
+        memberships: ITEM_GROUP,
+        filters: MAP_GROUP,
+    };
+
+    // enemies walk through each other
+    pub const ENEMY_COLLIDE_WITH_MAP_PLAYER_AND_WEAPONS: CollisionGroups = CollisionGroups {
+        memberships: ENEMY_GROUP,
+        filters: MAP_GROUP
+            .union(PLAYER_GROUP)
+            .union(WEAPON_GROUP)
+            .union(PROJECTILE_GROUP),
+    };
+
+    pub const WEAPON_COLLIDE_WITH_ENEMIES: CollisionGroups = CollisionGroups {
+        memberships: WEAPON_GROUP,
+        filters: ENEMY_GROUP,
+    };
+
+    pub const PROJECTILE_COLLIDE_WITH_MAP_AND_ENEMIES: CollisionGroups = CollisionGroups {
+        memberships: PROJECTILE_GROUP,
+        filters: MAP_GROUP.union(ENEMY_GROUP),
+    };
 }
 
 pub mod world {
     pub const GRAVITY: f32 = 3.;
     pub const PHYSICS_SCALE: f32 = 1.;
+    // seconds of a whole day and night
+    pub const DAY_LENGTH: f32 = 600.;
+    // share of the day passed when a world is entered, in the morning
+    pub const START_TIME_OF_DAY: f32 = 0.3;
+}
+
+pub mod mining {
+    use bevy::render::color::Color;
+
+    // damage dealt to a tile by one swing
+    pub const HIT_DAMAGE: f32 = 1.;
+    // seconds one swing takes with a tool of speed 1
+    pub const SWING_TIME: f32 = 0.1;
+    pub const HAND_SPEED: f32 = 1.;
+    pub const PROGRESS_COLOR: Color = Color::rgba(1., 1., 1., 0.8);
+    // the bar turns red when the selected pickaxe is too weak for the tile
+    pub const TOO_WEAK_COLOR: Color = Color::rgba(0.9, 0.2, 0.2, 0.8);
+    pub const TOO_WEAK_FONT_SIZE: f32 = 10.;
+    // frames following the intact tile in tiles_strip.png, each one more cracked
+    pub const DAMAGE_STAGES: u32 = 4;
+    // seconds a damaged tile has to be left alone before it starts healing
+    pub const HEAL_DELAY: f32 = 3.;
+    // damage healed per second
+    pub const HEAL_RATE: f32 = 1.;
+}
+
+pub mod items {
+    // size of one icon in items_strip.png
+    pub const ITEM_ICON_SIZE: f32 = 20.;
+    pub const DROP_SIZE: f32 = 10.;
+    pub const DROP_POP_SPEED: f32 = 60.;
+    pub const DROP_MERGE_RADIUS: f32 = 15.;
+    // seconds before a drop nobody picked up disappears, unless the world settings say otherwise
+    pub const DROP_LIFETIME: u32 = 300;
+    pub const DROP_MAGNET_SPEED: f32 = 150.;
+    pub const PICKUP_RADIUS: f32 = 50.;
+    pub const PICKUP_DISTANCE: f32 = 12.;
+}
+
+pub mod inventory {
+    pub const INVENTORY_SLOTS: usize = 40;
+    // the hotbar shows the first slots of the inventory
+    pub const HOTBAR_SLOTS: usize = 10;
+}
+
+pub mod hud {
+    use bevy::render::color::Color;
+
+    pub const FONT: &str = "fonts/DejaVuSans.ttf";
+    pub const HUD_MARGIN: f32 = 10.;
+    // hud is drawn in front of the camera, between it and the world
+    pub const Z_HUD: f32 = -1.;
+    pub const SLOT_SIZE: f32 = 28.;
+    pub const SLOT_GAP: f32 = 4.;
+    pub const SLOT_ICON_SIZE: f32 = 20.;
+    pub const SLOT_FONT_SIZE: f32 = 10.;
+    pub const SLOT_COLOR: Color = Color::rgba(0.1, 0.1, 0.3, 0.7);
+    pub const SELECTED_SLOT_COLOR: Color = Color::rgba(0.9, 0.8, 0.2, 0.9);
+    pub const PANEL_PADDING: f32 = 6.;
+    pub const PANEL_FONT_SIZE: f32 = 16.;
+    pub const PANEL_COLOR: Color = Color::rgba(0.1, 0.1, 0.3, 0.8);
+    pub const BUTTON_COLOR: Color = Color::rgba(0.2, 0.2, 0.5, 0.9);
+}
+
+pub mod health {
+    use bevy::render::color::Color;
+
+    pub const PLAYER_MAX_HEALTH: f32 = 100.;
+    // seconds without taking damage before the health starts coming back
+    pub const REGEN_DELAY: f32 = 5.;
+    // health regained per second
+    pub const REGEN_RATE: f32 = 2.;
+    // falling slower than this, in pixels per second, does not hurt
+    pub const SAFE_FALL_SPEED: f32 = 250.;
+    // damage for every pixel per second above the safe speed
+    pub const FALL_DAMAGE_PER_SPEED: f32 = 0.25;
+    // how much the contact normal has to point up for the contact to be a landing
+    pub const LANDING_NORMAL_Y: f32 = 0.7;
+    pub const HEALTH_BAR_WIDTH: f32 = 120.;
+    pub const HEALTH_BAR_HEIGHT: f32 = 12.;
+    pub const HEALTH_BAR_BACKGROUND: Color = Color::rgba(0.1, 0.1, 0.3, 0.7);
+    pub const HEALTH_BAR_COLOR: Color = Color::rgba(0.8, 0.15, 0.15, 0.9);
+    pub const HEALTH_FONT_SIZE: f32 = 10.;
+}
+
+pub mod death {
+    use bevy::render::color::Color;
+
+    // seconds the death screen is shown before the player respawns
+    pub const RESPAWN_TIME: f32 = 5.;
+    // share of every stack left on the ground where the player died
+    pub const EASY_DROP_SHARE: f32 = 0.;
+    pub const NORMAL_DROP_SHARE: f32 = 0.5;
+    pub const HARD_DROP_SHARE: f32 = 1.;
+    // seconds the items dropped on death stay on the ground, much longer than the other drops
+    pub const DEATH_PILE_LIFETIME: f32 = 1800.;
+    pub const DEATH_TITLE_COLOR: Color = Color::rgb(0.8, 0.1, 0.1);
+}
+
+pub mod enemies {
+    // enemy health and damage are multiplied by the strength of the world's difficulty
+    pub const EASY_ENEMY_STRENGTH: f32 = 0.75;
+    pub const NORMAL_ENEMY_STRENGTH: f32 = 1.;
+    pub const HARD_ENEMY_STRENGTH: f32 = 1.5;
+
+    pub const SLIME_HALF_SIZE: (f32, f32) = (10., 7.);
+    pub const SLIME_HEALTH: f32 = 30.;
+    pub const SLIME_DAMAGE: f32 = 10.;
+    // velocity given to the player hit by a slime, away from it
+    pub const SLIME_KNOCKBACK: (f32, f32) = (150., 80.);
+    // seconds between two hits of the same slime
+    pub const SLIME_ATTACK_COOLDOWN: f32 = 1.;
+    // seconds a slime rests on the ground between its hops
+    pub const SLIME_REST_TIME: f32 = 1.5;
+    pub const SLIME_JUMP_POWER: f32 = 110.;
+    // horizontal speed of a hop towards the player and of an aimless one
+    pub const SLIME_CHASE_SPEED: f32 = 70.;
+    pub const SLIME_WANDER_SPEED: f32 = 25.;
+    // pixels, slimes further from the player wander around
+    pub const SLIME_AGGRO_RANGE: f32 = 200.;
+    pub const SLIME_LOOT: (&str, u32) = ("gel", 2);
+}
+
+pub mod combat {
+    use bevy::render::color::Color;
+
+    // pixels from the player to the tip of a sword
+    pub const SWORD_REACH: f32 = 28.;
+    pub const SWORD_WIDTH: f32 = 6.;
+    // radians a sword sweeps through, centered on the cursor
+    pub const SWING_ARC: f32 = 2.;
+    pub const CRIT_MULTIPLIER: f32 = 2.;
+    pub const PROJECTILE_SIZE: f32 = 12.;
+    pub const PROJECTILE_RADIUS: f32 = 3.;
+    // pushes the hit enemy along the flight of the projectile
+    pub const PROJECTILE_KNOCKBACK: f32 = 80.;
+    // seconds a projectile flies before it disappears, and stays stuck in a tile
+    pub const PROJECTILE_LIFETIME: f32 = 10.;
+    pub const STUCK_LIFETIME: f32 = 5.;
+    // floating numbers shown over everything that takes damage
+    pub const DAMAGE_NUMBER_FONT_SIZE: f32 = 12.;
+    pub const DAMAGE_NUMBER_RISE_SPEED: f32 = 30.;
+    pub const DAMAGE_NUMBER_LIFETIME: f32 = 0.8;
+    pub const DAMAGE_NUMBER_COLOR: Color = Color::rgb(1., 0.6, 0.2);
+    pub const CRIT_NUMBER_COLOR: Color = Color::rgb(1., 0.2, 0.2);
+}
+
+pub mod menu {
+    use bevy::render::color::Color;
+
+    pub const TITLE_FONT_SIZE: f32 = 48.;
+    pub const MENU_FONT_SIZE: f32 = 24.;
+    pub const MENU_BUTTON_WIDTH: f32 = 280.;
+    pub const MENU_GAP: f32 = 12.;
+    pub const MENU_BACKGROUND: Color = Color::rgb(0.05, 0.05, 0.15);
+    // drawn over the world while the game is paused
+    pub const OVERLAY_COLOR: Color = Color::rgba(0., 0., 0., 0.6);
+    pub const HOVERED_BUTTON_COLOR: Color = Color::rgba(0.3, 0.3, 0.7, 0.9);
+    // the world and the character chosen in the selection screen
+    pub const SELECTED_BUTTON_COLOR: Color = Color::rgba(0.6, 0.5, 0.1, 0.9);
+    pub const SLOT_BUTTON_WIDTH: f32 = 360.;
+    pub const SLOT_INFO_FONT_SIZE: f32 = 14.;
+    pub const SMALL_BUTTON_WIDTH: f32 = 84.;
+    pub const NAME_MAX_LENGTH: usize = 24;
+    // digits of the biggest u64
+    pub const SEED_MAX_LENGTH: usize = 20;
+    pub const MENU_ERROR_COLOR: Color = Color::rgb(0.9, 0.3, 0.3);
+    // bindings shared by more than one action
+    pub const CONFLICT_BUTTON_COLOR: Color = Color::rgba(0.7, 0.15, 0.15, 0.9);
+    pub const ACTION_LABEL_WIDTH: f32 = 200.;
+    pub const BINDING_BUTTON_WIDTH: f32 = 150.;
+}
+
+pub mod gamepad {
+    use bevy::render::color::Color;
+
+    // stick values closer to the center than this are ignored
+    pub const STICK_DEADZONE: f32 = 0.3;
+    // the virtual cursor in pixels per second with the stick fully tilted
+    pub const CURSOR_SPEED: f32 = 160.;
+    // furthest distance of the virtual cursor from the player in pixels
+    pub const CURSOR_RANGE: f32 = 80.;
+    pub const CURSOR_SIZE: f32 = 6.;
+    pub const CURSOR_COLOR: Color = Color::rgba(1., 1., 1., 0.9);
+}
+
+pub mod stations {
+    // size of one frame in stations_strip.png, stations are drawn from its bottom left corner
+    pub const STATION_SPRITE_SIZE: f32 = 40.;
+    pub const STATION_SPRITES: usize = 4;
+    // tiles between the player and the stations in reach
+    pub const STATION_RANGE: f32 = 4.;
+    // damage a pickaxe has to deal before a station gets taken down
+    pub const STATION_HARDNESS: f32 = 6.;
+    // interacting with a placed bed makes it the respawn point
+    pub const BED_ITEM: &str = "bed";
+}
+
+pub mod save {
+    // bumped whenever the format of the world or the character file changes,
+    // older saves are migrated on load
+    pub const WORLD_VERSION: u32 = 3;
+    pub const PLAYER_VERSION: u32 = 2;
+    // relative to the directory the game is started from
+    pub const SAVE_DIR: &str = "saves";
+    pub const WORLD_FILE: &str = "world.ron";
+    // every chunk of the world is a file in this directory next to the world file
+    pub const CHUNKS_DIR: &str = "chunks";
+    pub const PLAYER_FILE: &str = "player.ron";
+    // seconds between two autosaves, unless the settings file says otherwise
+    pub const AUTOSAVE_INTERVAL: f32 = 120.;
+    // older copies of every save file kept next to it
+    pub const BACKUP_COUNT: usize = 3;
+    // every world and every character gets its own directory in these
+    pub const WORLDS_DIR: &str = "worlds";
+    pub const PLAYERS_DIR: &str = "players";
+    // name, settings and play time, read without the much bigger save itself
+    pub const META_FILE: &str = "meta.ron";
+    // bindings of the actions, shared by all the worlds and characters
+    pub const SETTINGS_FILE: &str = "settings.ron";
+    // bumped whenever a default binding is added to an action which existed already
+    pub const SETTINGS_VERSION: u32 = 2;
+    // the saved layers of the world are split into square chunks of this size
+    pub const SAVE_CHUNK_SIZE: u32 = 32;
 }
 
 pub mod player {
     pub const JUMP_POWER: f32 = 120.;
     pub const MOVEMENT_SPEED: f32 = 100.;
     pub const VISION_RADIUS: u32 = 8; // tiles seen in each direction
+    pub const PLAYER_HALF_SIZE: f32 = 8.;
+    pub const PLACE_RANGE: f32 = 4.; // tiles in each direction
+    pub const STARTING_ITEMS: [(&str, u32); 1] = [("wooden_pickaxe", 1)];
+    // where new characters and characters coming from another world appear
+    pub const SPAWN_POSITION: (f32, f32) = (0., 50.);
 }
 
 // map constants
@@ -51,25 +319,31 @@ pub mod map {
         TilePos, TilemapGridSize, TilemapSize, TilemapTileSize, TilemapType,
     };
 
-    const MAP_WIDTH: u32 = 150;
+    // widths of the size presets a world can be created with
+    pub const SMALL_MAP_WIDTH: u32 = 100;
+    pub const MEDIUM_MAP_WIDTH: u32 = 150;
+    pub const LARGE_MAP_WIDTH: u32 = 250;
     const MAP_DEPTH: u32 = 32;
     pub const BUILDING_HEIGHT: u32 = 15;
-    pub const MAP_SIZE: TilemapSize = TilemapSize {
-        x: MAP_WIDTH,
-        y: MAP_DEPTH + BUILDING_HEIGHT,
-    };
+    pub const MAP_HEIGHT: u32 = MAP_DEPTH + BUILDING_HEIGHT;
 
     pub const MAP_TYPE: TilemapType = TilemapType::Square;
     pub const TILE_SIZE: TilemapTileSize = TilemapTileSize { x: 20., y: 20. };
     pub const GRID_SIZE: TilemapGridSize = TilemapGridSize { x: 20., y: 20. };
     pub const COLLIDER_SIZE: Vec2 = Vec2::new(TILE_SIZE.x / 2., TILE_SIZE.y / 2.);
+    // tiles are merged into colliders inside of square chunks of this size
+    pub const COLLIDER_CHUNK_SIZE: u32 = 16;
 
     pub const MAX_CAVE_SIZE: u32 = 150;
 
+    // tiles below the top of the ground where the underground and the caverns begin
+    pub const UNDERGROUND_DEPTH: u32 = 5;
+    pub const CAVERNS_DEPTH: u32 = 20;
+
     // counts x and y translation of map, that top middle tile of the map is located in (0.0)
-    pub fn map_transform_vec2() -> Vec2 {
+    pub fn map_transform_vec2(map_size: &TilemapSize) -> Vec2 {
         let low = TilePos::new(0, 0).center_in_world(&GRID_SIZE, &MAP_TYPE);
-        let high = TilePos::new(MAP_SIZE.x - 1, MAP_SIZE.y - 1 - BUILDING_HEIGHT)
+        let high = TilePos::new(map_size.x - 1, map_size.y - 1 - BUILDING_HEIGHT)
             .center_in_world(&GRID_SIZE, &MAP_TYPE);
 
         let diff = high - low;
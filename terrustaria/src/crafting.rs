@@ -0,0 +1,303 @@
+use bevy::prelude::*;
+use serde::Deserialize;
+
+use crate::actions::{Action, Actions};
+use crate::constants::hud::*;
+use crate::game_state::WorldEntity;
+use crate::inventory::{Inventory, ItemStack};
+use crate::item::{ItemId, ItemRegistry};
+use crate::player::Player;
+use crate::stations::NearbyStations;
+
+#[derive(Deserialize)]
+pub struct Recipe {
+    pub ingredients: Vec<ItemStack>,
+    pub outputs: Vec<ItemStack>,
+    #[serde(default)]
+    pub station: Option<ItemId>,
+}
+
+#[derive(Resource)]
+pub struct RecipeRegistry {
+    recipes: Vec<Recipe>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum CraftError {
+    MissingStation,
+    MissingIngredients,
+    InventoryFull,
+}
+
+#[derive(Component, Default)]
+pub struct CraftingPanel {
+    // indices of the recipes the panel currently shows
+    shown: Vec<usize>,
+}
+
+#[derive(Component)]
+pub struct RecipeButton(usize);
+
+impl Recipe {
+    pub fn has_ingredients(&self, inventory: &Inventory) -> bool {
+        self.ingredients
+            .iter()
+            .all(|ingredient| inventory.count(&ingredient.item) >= ingredient.count)
+    }
+
+    pub fn has_station(&self, stations: &[ItemId]) -> bool {
+        self.station
+            .as_ref()
+            .map_or(true, |station| stations.contains(station))
+    }
+}
+
+impl RecipeRegistry {
+    pub fn from_ron(data: &str) -> Result<Self, ron::error::SpannedError> {
+        Ok(Self {
+            recipes: ron::from_str(data)?,
+        })
+    }
+
+    pub fn get(&self, idx: usize) -> Option<&Recipe> {
+        self.recipes.get(idx)
+    }
+
+    // indices of the recipes which can be crafted right now
+    pub fn craftable(&self, inventory: &Inventory, stations: &[ItemId]) -> Vec<usize> {
+        self.recipes
+            .iter()
+            .enumerate()
+            .filter(|(_, recipe)| recipe.has_station(stations) && recipe.has_ingredients(inventory))
+            .map(|(idx, _)| idx)
+            .collect()
+    }
+}
+
+impl Default for RecipeRegistry {
+    fn default() -> Self {
+        Self::from_ron(include_str!("../assets/data/recipes.ron")).expect("invalid recipes.ron")
+    }
+}
+
+// takes the ingredients and gives the outputs, or leaves the inventory untouched if it cannot
+pub fn craft(
+    inventory: &mut Inventory,
+    registry: &ItemRegistry,
+    recipe: &Recipe,
+    stations: &[ItemId],
+) -> Result<(), CraftError> {
+    if !recipe.has_station(stations) {
+        return Err(CraftError::MissingStation);
+    }
+    if !recipe.has_ingredients(inventory) {
+        return Err(CraftError::MissingIngredients);
+    }
+
+    // the outputs may fit only into the room freed by the ingredients
+    let mut crafted = inventory.clone();
+    for ingredient in recipe.ingredients.iter() {
+        crafted.remove(&ingredient.item, ingredient.count);
+    }
+    for output in recipe.outputs.iter() {
+        if crafted.add(registry, &output.item, output.count) > 0 {
+            return Err(CraftError::InventoryFull);
+        }
+    }
+
+    *inventory = crafted;
+    Ok(())
+}
+
+fn describe_stacks(registry: &ItemRegistry, stacks: &[ItemStack]) -> String {
+    stacks
+        .iter()
+        .map(|stack| {
+            let name = registry
+                .get(&stack.item)
+                .map_or(stack.item.0.as_str(), |def| def.name.as_str());
+            if stack.count > 1 {
+                format!("{} {name}", stack.count)
+            } else {
+                String::from(name)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+pub fn spawn_crafting_panel(mut commands: Commands) {
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                display: Display::None,
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    right: Val::Px(HUD_MARGIN),
+                    top: Val::Px(HUD_MARGIN),
+                    ..default()
+                },
+                flex_direction: FlexDirection::Column,
+                padding: UiRect::all(Val::Px(PANEL_PADDING)),
+                ..default()
+            },
+            background_color: PANEL_COLOR.into(),
+            ..default()
+        })
+        .insert(CraftingPanel::default())
+        .insert(Name::new("CraftingPanel"))
+        .insert(WorldEntity);
+}
+
+pub fn toggle_crafting_panel(
+    actions: Actions,
+    mut panel_q: Query<&mut Style, With<CraftingPanel>>,
+) {
+    if !actions.just_pressed(Action::OpenInventory) {
+        return;
+    }
+    for mut style in panel_q.iter_mut() {
+        style.display = match style.display {
+            Display::None => Display::Flex,
+            Display::Flex => Display::None,
+        };
+    }
+}
+
+// lists the recipes craftable from the current inventory, one button for each
+pub fn update_crafting_panel(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    recipes: Res<RecipeRegistry>,
+    registry: Res<ItemRegistry>,
+    nearby_stations: Res<NearbyStations>,
+    player_q: Query<&Inventory, With<Player>>,
+    mut panel_q: Query<(Entity, &mut CraftingPanel)>,
+) {
+    let inventory = player_q.single();
+    let (panel_entity, mut panel) = panel_q.single_mut();
+
+    let craftable = recipes.craftable(inventory, &nearby_stations.0);
+    if craftable == panel.shown {
+        return;
+    }
+
+    let font = asset_server.load(FONT);
+    let text_style = TextStyle {
+        font,
+        font_size: PANEL_FONT_SIZE,
+        color: Color::WHITE,
+    };
+
+    commands.entity(panel_entity).despawn_descendants();
+    commands.entity(panel_entity).with_children(|parent| {
+        parent.spawn(TextBundle::from_section("Crafting", text_style.clone()));
+        for idx in craftable.iter() {
+            let Some(recipe) = recipes.get(*idx) else {
+                continue;
+            };
+            let label = format!(
+                "{} ({})",
+                describe_stacks(&registry, &recipe.outputs),
+                describe_stacks(&registry, &recipe.ingredients)
+            );
+            parent
+                .spawn(ButtonBundle {
+                    style: Style {
+                        margin: UiRect::top(Val::Px(PANEL_PADDING)),
+                        padding: UiRect::all(Val::Px(PANEL_PADDING)),
+                        ..default()
+                    },
+                    background_color: BUTTON_COLOR.into(),
+                    ..default()
+                })
+                .insert(RecipeButton(*idx))
+                .with_children(|button| {
+                    button.spawn(TextBundle::from_section(label, text_style.clone()));
+                });
+        }
+    });
+
+    panel.shown = craftable;
+}
+
+pub fn craft_clicked_recipe(
+    recipes: Res<RecipeRegistry>,
+    registry: Res<ItemRegistry>,
+    nearby_stations: Res<NearbyStations>,
+    interaction_q: Query<(&Interaction, &RecipeButton), Changed<Interaction>>,
+    mut player_q: Query<&mut Inventory, With<Player>>,
+) {
+    let mut inventory = player_q.single_mut();
+    for (interaction, button) in interaction_q.iter() {
+        if *interaction != Interaction::Clicked {
+            continue;
+        }
+        if let Some(recipe) = recipes.get(button.0) {
+            if let Err(err) = craft(&mut inventory, &registry, recipe, &nearby_stations.0) {
+                info!("cannot craft: {err:?}");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::item::test_registry;
+
+    fn workbench_recipe() -> Recipe {
+        Recipe {
+            ingredients: vec![
+                ItemStack::new(ItemId::new("dirt"), 10),
+                ItemStack::new(ItemId::new("stone"), 5),
+            ],
+            outputs: vec![ItemStack::new(ItemId::new("workbench"), 1)],
+            station: None,
+        }
+    }
+
+    #[test]
+    fn short_ingredient_leaves_inventory_unchanged() {
+        let registry = test_registry();
+        let mut inventory = Inventory::new(4);
+        inventory.add(&registry, &ItemId::new("dirt"), 10);
+        inventory.add(&registry, &ItemId::new("stone"), 4);
+        let before = inventory.clone();
+
+        let result = craft(&mut inventory, &registry, &workbench_recipe(), &[]);
+
+        assert_eq!(result, Err(CraftError::MissingIngredients));
+        assert_eq!(inventory, before);
+    }
+
+    #[test]
+    fn outputs_not_fitting_consume_nothing() {
+        let registry = test_registry();
+        // both slots stay full after the ingredients are taken
+        let mut inventory = Inventory::new(2);
+        inventory.add(&registry, &ItemId::new("dirt"), 20);
+        inventory.add(&registry, &ItemId::new("stone"), 10);
+        let before = inventory.clone();
+
+        let result = craft(&mut inventory, &registry, &workbench_recipe(), &[]);
+
+        assert_eq!(result, Err(CraftError::InventoryFull));
+        assert_eq!(inventory, before);
+    }
+
+    #[test]
+    fn craft_takes_exact_ingredients_and_adds_outputs() {
+        let registry = test_registry();
+        let mut inventory = Inventory::new(4);
+        inventory.add(&registry, &ItemId::new("dirt"), 12);
+        inventory.add(&registry, &ItemId::new("stone"), 5);
+
+        let result = craft(&mut inventory, &registry, &workbench_recipe(), &[]);
+
+        assert_eq!(result, Ok(()));
+        assert_eq!(inventory.count(&ItemId::new("dirt")), 2);
+        assert_eq!(inventory.count(&ItemId::new("stone")), 0);
+        assert_eq!(inventory.count(&ItemId::new("workbench")), 1);
+    }
+}
@@ -1,6 +1,8 @@
-use crate::player::MainCamera;
+use crate::gamepad::GamepadCursor;
+use crate::player::{MainCamera, Player};
+use bevy::ecs::system::SystemParam;
 use bevy::render::camera::RenderTarget;
-use bevy::{ecs::system::Resource, prelude::*};
+use bevy::{ecs::system::Resource, math::Vec4Swizzles, prelude::*};
 
 #[derive(Resource)]
 pub struct CursorPos(pub(crate) Vec3);
@@ -11,12 +13,43 @@ impl Default for CursorPos {
     }
 }
 
-// update the cursor position resource system
+// whether the mouse or the gamepad cursor points at a button,
+// a click there is meant for the ui and not for the world behind it
+#[derive(SystemParam)]
+pub struct UiPointer<'w, 's> {
+    interaction_q: Query<'w, 's, &'static Interaction, With<Button>>,
+}
+
+impl UiPointer<'_, '_> {
+    pub fn over_ui(&self) -> bool {
+        self.interaction_q
+            .iter()
+            .any(|interaction| *interaction != Interaction::None)
+    }
+}
+
+// cursor position in the coordinates of a tilemap with the given transform
+pub fn cursor_in_map_pos(cursor_pos: &CursorPos, map_transform: &Transform) -> Vec2 {
+    let cursor_pos = Vec4::from((cursor_pos.0, 1.0));
+    let cursor_in_map_pos = map_transform.compute_matrix().inverse() * cursor_pos;
+    cursor_in_map_pos.xy()
+}
+
+// update the cursor position resource system, the virtual cursor of a gamepad wins over the mouse
 pub fn update_cursor_pos(
     wnds: Res<Windows>,
     q_camera: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
+    q_player: Query<&Transform, With<Player>>,
+    gamepad_cursor: Res<GamepadCursor>,
     mut cursor_pos: ResMut<CursorPos>,
 ) {
+    if let Ok(player_transform) = q_player.get_single() {
+        if let Some(world_pos) = gamepad_cursor.position(player_transform.translation.truncate()) {
+            *cursor_pos = CursorPos(world_pos.extend(0.));
+            return;
+        }
+    }
+
     // get the camera info and transform
     // assuming there is exactly one main camera entity, so query::single() is OK
     let (camera, camera_transform) = q_camera.single();
@@ -0,0 +1,314 @@
+use bevy::prelude::*;
+use bevy_ecs_tilemap::prelude::*;
+use bevy_rapier2d::prelude::*;
+
+use crate::actions::{Action, Actions};
+use crate::constants::{
+    death::*,
+    hud::FONT,
+    map::{GRID_SIZE, MAP_TYPE, TILE_SIZE},
+    menu::*,
+    player::SPAWN_POSITION,
+    stations::{BED_ITEM, STATION_RANGE},
+};
+use crate::cursor::{cursor_in_map_pos, CursorPos};
+use crate::drops::spawn_item_drop;
+use crate::game_state::AppState;
+use crate::health::{FallTracker, Health};
+use crate::inventory::Inventory;
+use crate::item::{ItemId, ItemRegistry, ItemSprites};
+use crate::map::WithColliders;
+use crate::menu::MenuRoot;
+use crate::player::Player;
+use crate::save::{save_and_report, GameSnapshot};
+use crate::save_slots::{delete_slot, players_dir, CurrentSlots};
+use crate::stations::{CraftingStation, StationMap};
+use crate::world_settings::WorldSettings;
+
+// where the player comes back to life, the spawn of the world without a bed
+#[derive(Component, Default)]
+pub struct SpawnPoint {
+    pub bed: Option<TilePos>,
+}
+
+// inserted when the player dies, the death screen is shown until the timer finishes
+#[derive(Resource)]
+pub struct Death {
+    cause: &'static str,
+    respawn: Timer,
+    // the character is gone and the game goes back to the main menu
+    permanent: bool,
+}
+
+#[derive(Component)]
+pub struct RespawnCountdown;
+
+// the bed still stands where it was when the spawn point was set
+fn bed_at(cell: TilePos, station_map: &StationMap, station_q: &Query<&CraftingStation>) -> bool {
+    station_map
+        .get(&cell)
+        .and_then(|entity| station_q.get(entity).ok())
+        .map_or(false, |station| {
+            *station.item() == ItemId::new(BED_ITEM) && station.origin() == cell
+        })
+}
+
+// interacting with a bed in reach makes it the spawn point
+pub fn set_spawn_at_bed(
+    actions: Actions,
+    cursor_pos: Res<CursorPos>,
+    tilemap_q: Query<(&TileStorage, &Transform), With<WithColliders>>,
+    station_map: Res<StationMap>,
+    station_q: Query<&CraftingStation>,
+    mut player_q: Query<(&Transform, &mut SpawnPoint), (With<Player>, Without<WithColliders>)>,
+) {
+    if !actions.just_pressed(Action::Interact) {
+        return;
+    }
+    let Ok((tile_storage, map_transform)) = tilemap_q.get_single() else {
+        return;
+    };
+    let Ok((player_transform, mut spawn_point)) = player_q.get_single_mut() else {
+        return;
+    };
+    let cursor_in_map_pos = cursor_in_map_pos(&cursor_pos, map_transform);
+    let Some(tile_pos) = TilePos::from_world_pos(
+        &cursor_in_map_pos,
+        &tile_storage.size,
+        &GRID_SIZE,
+        &MAP_TYPE,
+    ) else {
+        return;
+    };
+    let Some(station) = station_map
+        .get(&tile_pos)
+        .and_then(|entity| station_q.get(entity).ok())
+    else {
+        return;
+    };
+    if *station.item() != ItemId::new(BED_ITEM) {
+        return;
+    }
+    let bed_pos = station.origin().center_in_world(&GRID_SIZE, &MAP_TYPE)
+        + map_transform.translation.truncate();
+    let in_range =
+        bed_pos.distance(player_transform.translation.truncate()) <= STATION_RANGE * TILE_SIZE.x;
+    if in_range && spawn_point.bed != Some(station.origin()) {
+        spawn_point.bed = Some(station.origin());
+        info!("spawn point set");
+    }
+}
+
+// leaves a share of the inventory on the ground, depending on the difficulty of the world
+fn drop_death_pile(
+    commands: &mut Commands,
+    registry: &ItemRegistry,
+    item_sprites: &ItemSprites,
+    inventory: &mut Inventory,
+    share: f32,
+    position: Vec2,
+) {
+    if share <= 0. {
+        return;
+    }
+    let stacks: Vec<_> = inventory.slots().iter().flatten().cloned().collect();
+    for stack in stacks {
+        let count = ((stack.count as f32 * share).ceil() as u32).min(stack.count);
+        if count == 0 || inventory.remove(&stack.item, count) > 0 {
+            continue;
+        }
+        spawn_item_drop(
+            commands,
+            registry,
+            item_sprites,
+            stack.item,
+            count,
+            position,
+            DEATH_PILE_LIFETIME,
+        );
+    }
+}
+
+pub fn kill_player(
+    mut commands: Commands,
+    mut state: ResMut<State<AppState>>,
+    settings: Res<WorldSettings>,
+    registry: Res<ItemRegistry>,
+    item_sprites: Res<ItemSprites>,
+    mut player_q: Query<(&Transform, &Health, &mut Inventory), With<Player>>,
+) {
+    let Ok((transform, health, mut inventory)) = player_q.get_single_mut() else {
+        return;
+    };
+    if !health.is_dead() {
+        return;
+    }
+    drop_death_pile(
+        &mut commands,
+        &registry,
+        &item_sprites,
+        &mut inventory,
+        settings.difficulty.death_drop_share(),
+        transform.translation.truncate(),
+    );
+    commands.insert_resource(Death {
+        cause: health
+            .last_damage()
+            .map_or("ran out of health", |source| source.cause()),
+        respawn: Timer::from_seconds(RESPAWN_TIME, TimerMode::Once),
+        permanent: settings.difficulty.permanent_death(),
+    });
+    if let Err(err) = state.push(AppState::Dead) {
+        warn!("cannot show the death screen: {err:?}");
+    }
+}
+
+// the world is saved one last time before the character is deleted,
+// nothing is saved afterwards because there are no slots anymore
+pub fn delete_dead_character(
+    mut commands: Commands,
+    death: Res<Death>,
+    mut snapshot: GameSnapshot,
+    slots: Option<Res<CurrentSlots>>,
+) {
+    if !death.permanent {
+        return;
+    }
+    let Some(slots) = slots else {
+        return;
+    };
+    save_and_report(&mut snapshot, "permanent death");
+    match delete_slot(&players_dir(), &slots.player) {
+        Ok(()) => info!("the character {} is gone for good", slots.player),
+        Err(err) => error!("cannot delete the character {}: {err}", slots.player),
+    }
+    commands.remove_resource::<CurrentSlots>();
+}
+
+pub fn spawn_death_screen(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    death: Res<Death>,
+) {
+    let font = asset_server.load(FONT);
+    let text_style = TextStyle {
+        font: font.clone(),
+        font_size: MENU_FONT_SIZE,
+        color: Color::WHITE,
+    };
+
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.), Val::Percent(100.)),
+                flex_direction: FlexDirection::Column,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            background_color: OVERLAY_COLOR.into(),
+            ..default()
+        })
+        .insert(MenuRoot)
+        .insert(Name::new("DeathScreen"))
+        .with_children(|parent| {
+            parent.spawn(
+                TextBundle::from_section(
+                    "You died",
+                    TextStyle {
+                        font,
+                        font_size: TITLE_FONT_SIZE,
+                        color: DEATH_TITLE_COLOR,
+                    },
+                )
+                .with_style(Style {
+                    margin: UiRect::bottom(Val::Px(MENU_GAP * 2.)),
+                    ..default()
+                }),
+            );
+            parent.spawn(
+                TextBundle::from_section(format!("You {}", death.cause), text_style.clone())
+                    .with_style(Style {
+                        margin: UiRect::bottom(Val::Px(MENU_GAP)),
+                        ..default()
+                    }),
+            );
+            parent
+                .spawn(TextBundle::from_section("", text_style))
+                .insert(RespawnCountdown);
+        });
+}
+
+// a bed which is not there anymore is forgotten
+fn respawn_position(
+    spawn_point: &mut SpawnPoint,
+    tilemap_q: &Query<&Transform, (With<TileStorage>, With<WithColliders>)>,
+    station_map: &StationMap,
+    station_q: &Query<&CraftingStation>,
+) -> Vec2 {
+    let world_spawn = Vec2::new(SPAWN_POSITION.0, SPAWN_POSITION.1);
+    let (Some(bed), Ok(map_transform)) = (spawn_point.bed, tilemap_q.get_single()) else {
+        return world_spawn;
+    };
+    if !bed_at(bed, station_map, station_q) {
+        spawn_point.bed = None;
+        return world_spawn;
+    }
+    // on top of the middle of the bed
+    bed.center_in_world(&GRID_SIZE, &MAP_TYPE)
+        + map_transform.translation.truncate()
+        + Vec2::new(TILE_SIZE.x / 2., TILE_SIZE.y)
+}
+
+#[allow(clippy::type_complexity)]
+pub fn count_down_respawn(
+    time: Res<Time>,
+    mut state: ResMut<State<AppState>>,
+    mut death: ResMut<Death>,
+    mut countdown_q: Query<&mut Text, With<RespawnCountdown>>,
+    tilemap_q: Query<&Transform, (With<TileStorage>, With<WithColliders>)>,
+    station_map: Res<StationMap>,
+    station_q: Query<&CraftingStation>,
+    mut player_q: Query<
+        (
+            &mut Transform,
+            &mut Velocity,
+            &mut Health,
+            &mut FallTracker,
+            &mut SpawnPoint,
+        ),
+        (With<Player>, Without<WithColliders>),
+    >,
+) {
+    death.respawn.tick(time.delta());
+    let left = death.respawn.remaining_secs().ceil();
+    for mut text in countdown_q.iter_mut() {
+        text.sections[0].value = if death.permanent {
+            format!("Your character is lost, returning to the menu in {left}")
+        } else {
+            format!("Respawning in {left}")
+        };
+    }
+    if !death.respawn.finished() {
+        return;
+    }
+
+    if death.permanent {
+        if let Err(err) = state.replace(AppState::MainMenu) {
+            warn!("cannot return to the menu: {err:?}");
+        }
+        return;
+    }
+    if let Ok((mut transform, mut velocity, mut health, mut fall, mut spawn_point)) =
+        player_q.get_single_mut()
+    {
+        let position = respawn_position(&mut spawn_point, &tilemap_q, &station_map, &station_q);
+        transform.translation = position.extend(transform.translation.z);
+        *velocity = Velocity::zero();
+        *fall = FallTracker::default();
+        health.revive();
+    }
+    if let Err(err) = state.pop() {
+        warn!("cannot respawn: {err:?}");
+    }
+}
@@ -1,14 +1,100 @@
-use crate::constants::map::{map_transform_vec2, TILE_SIZE};
-use crate::cursor::CursorPos;
-use crate::map::WithColliders;
+use crate::actions::{Action, Actions};
+use crate::constants::depth::Z_MINING_PROGRESS;
+use crate::constants::hud::FONT;
+use crate::constants::map::TILE_SIZE;
+use crate::constants::mining::*;
+use crate::constants::stations::STATION_HARDNESS;
+use crate::cursor::{cursor_in_map_pos, CursorPos, UiPointer};
+use crate::game_state::WorldEntity;
+use crate::hotbar::Hotbar;
+use crate::inventory::Inventory;
+use crate::item::{ItemKind, ItemRegistry};
+use crate::map::{remove_foreground_tile, TileChanged, TileDestroyed, WithColliders};
 use crate::player::Player;
-use bevy::{math::Vec4Swizzles, prelude::*};
+use crate::stations::{remove_station, CraftingStation, StationDestroyed, StationMap};
+use crate::tile::TileCollection;
+use crate::tile_damage::TileDamage;
+use bevy::{prelude::*, sprite::Anchor};
 use bevy_ecs_tilemap::prelude::*;
 use bevy_rapier2d::prelude::Velocity;
-use std::thread::sleep;
+use std::time::Duration;
 
+#[derive(Component)]
+pub struct Miner {
+    swing_timer: Timer,
+}
+
+// bar drawn under the tile being mined
+#[derive(Component)]
+pub struct MiningProgress;
+
+// text shown above a tile the selected pickaxe is too weak for
+#[derive(Component)]
+pub struct MiningRefused;
+
+impl Miner {
+    // speed is a number of swings per SWING_TIME, given by the tool
+    pub fn new(speed: f32) -> Self {
+        Self {
+            swing_timer: Timer::from_seconds(SWING_TIME / speed, TimerMode::Repeating),
+        }
+    }
+
+    // swings get faster or slower when another pickaxe is selected
+    pub fn set_speed(&mut self, speed: f32) {
+        let duration = Duration::from_secs_f32(SWING_TIME / speed);
+        if self.swing_timer.duration() != duration {
+            self.swing_timer.set_duration(duration);
+            self.swing_timer.reset();
+        }
+    }
+}
+
+impl Default for Miner {
+    fn default() -> Self {
+        Self::new(HAND_SPEED)
+    }
+}
+
+pub fn spawn_mining_progress(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands
+        .spawn(SpriteBundle {
+            sprite: Sprite {
+                color: PROGRESS_COLOR,
+                custom_size: Some(Vec2::new(TILE_SIZE.x, 3.)),
+                anchor: Anchor::CenterLeft,
+                ..default()
+            },
+            visibility: Visibility { is_visible: false },
+            ..default()
+        })
+        .insert(MiningProgress)
+        .insert(Name::new("MiningProgress"))
+        .insert(WorldEntity);
+
+    commands
+        .spawn(Text2dBundle {
+            text: Text::from_section(
+                "Needs a stronger pickaxe",
+                TextStyle {
+                    font: asset_server.load(FONT),
+                    font_size: TOO_WEAK_FONT_SIZE,
+                    color: TOO_WEAK_COLOR,
+                },
+            )
+            .with_alignment(TextAlignment::BOTTOM_CENTER),
+            visibility: Visibility { is_visible: false },
+            ..default()
+        })
+        .insert(MiningRefused)
+        .insert(Name::new("MiningRefused"))
+        .insert(WorldEntity);
+}
+
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
 pub fn destroy_tile_after_click(
     mut commands: Commands,
+    time: Res<Time>,
     cursor_pos: Res<CursorPos>,
     mut tilemap_q: Query<
         (
@@ -20,53 +106,162 @@ pub fn destroy_tile_after_click(
         ),
         With<WithColliders>,
     >,
-    mut tile_q: Query<&mut TileTextureIndex>,
-    player_q: Query<(&Player, &Velocity)>,
-    position_q: Query<&GlobalTransform, With<Player>>,
-    mouse: Res<Input<MouseButton>>,
+    tile_q: Query<(&TileTextureIndex, Option<&TileDamage>)>,
+    station_q: Query<(&CraftingStation, Option<&TileDamage>)>,
+    mut station_map: ResMut<StationMap>,
+    tile_types: Res<TileCollection>,
+    registry: Res<ItemRegistry>,
+    mut player_q: Query<
+        (&GlobalTransform, &Velocity, &Inventory, &Hotbar, &mut Miner),
+        With<Player>,
+    >,
+    mut progress_q: Query<
+        (&mut Transform, &mut Visibility, &mut Sprite),
+        (With<MiningProgress>, Without<WithColliders>),
+    >,
+    mut refused_q: Query<
+        (&mut Transform, &mut Visibility),
+        (
+            With<MiningRefused>,
+            Without<MiningProgress>,
+            Without<WithColliders>,
+        ),
+    >,
+    actions: Actions,
+    ui_pointer: UiPointer,
+    mut tile_changes: EventWriter<TileChanged>,
+    mut destroyed_tiles: EventWriter<TileDestroyed>,
+    mut destroyed_stations: EventWriter<StationDestroyed>,
 ) {
-    let player_pos = position_q.single().translation();
+    // we have only one player
+    let (player_transform, player_velocity, inventory, hotbar, mut miner) = player_q.single_mut();
+    let player_pos = player_transform.translation();
+    let player_moving_fast = player_velocity.linvel.length() > 0.2;
+    let pickaxe = hotbar
+        .selected_item(inventory, &registry)
+        .and_then(|item| match item.kind {
+            ItemKind::Pickaxe { power, speed } => Some((power, speed)),
+            _ => None,
+        });
 
-    for (map_size, grid_size, map_type, mut tile_storage, map_transform) in tilemap_q.iter_mut() {
-        let cursor_pos: Vec3 = cursor_pos.0;
-        let cursor_in_map_pos: Vec2 = {
-            let cursor_pos = Vec4::from((cursor_pos, 1.0));
-            let cursor_in_map_pos = map_transform.compute_matrix().inverse() * cursor_pos;
-            cursor_in_map_pos.xy()
-        };
+    let (mut progress_transform, mut progress_visibility, mut progress_sprite) =
+        progress_q.single_mut();
+    progress_visibility.is_visible = false;
+    let (mut refused_transform, mut refused_visibility) = refused_q.single_mut();
+    refused_visibility.is_visible = false;
 
-        // we have only one player
-        let (_, player_velocity) = player_q.single();
-        let player_moving_fast = player_velocity.linvel.length() > 0.2;
+    // skip when mouse is not pressed and destroy only with a pickaxe when player is not moving fast
+    let Some((power, speed)) = pickaxe.filter(|_| {
+        actions.pressed(Action::UseItem) && !player_moving_fast && !ui_pointer.over_ui()
+    }) else {
+        miner.swing_timer.reset();
+        return;
+    };
+    miner.set_speed(speed);
 
-        // skip when mouse is not pressed and destroy only when player is not moving fast
-        if !mouse.pressed(MouseButton::Left) || player_moving_fast {
-            continue;
-        }
+    // a long frame can fit more than one swing
+    miner.swing_timer.tick(time.delta());
+    let swings = miner.swing_timer.times_finished_this_tick();
+
+    for (map_size, grid_size, map_type, mut tile_storage, map_transform) in tilemap_q.iter_mut() {
+        let cursor_in_map_pos = cursor_in_map_pos(&cursor_pos, map_transform);
 
         // check if player is in range of a tile
         let eps_x: f32 = 1.5 * TILE_SIZE.x;
         let eps_y: f32 = 1.5 * TILE_SIZE.y;
-        let dif_x: f32 = cursor_in_map_pos.x - player_pos.x + map_transform_vec2().x;
-        let dif_y: f32 = cursor_in_map_pos.y - player_pos.y + map_transform_vec2().y;
+        let dif_x: f32 = cursor_in_map_pos.x - player_pos.x + map_transform.translation.x;
+        let dif_y: f32 = cursor_in_map_pos.y - player_pos.y + map_transform.translation.y;
         if dif_x.abs() > eps_x || dif_y.abs() > eps_y {
             continue;
         }
 
-        if let Some(tile_pos) =
+        let Some(tile_pos) =
             TilePos::from_world_pos(&cursor_in_map_pos, map_size, grid_size, map_type)
-        {
-            if let Some(tile_entity) = tile_storage.get(&tile_pos) {
-                if let Ok(mut tile_texture) = tile_q.get_mut(tile_entity) {
-                    if tile_texture.0 % 5 == 4 {
-                        commands.entity(tile_entity).despawn_recursive();
-                        tile_storage.remove(&tile_pos);
-                    } else {
-                        tile_texture.0 += 1;
-                        sleep(std::time::Duration::from_millis(100));
-                    }
-                }
+        else {
+            continue;
+        };
+        // a station is mined as a whole from any of its cells
+        let (tile_entity, tile_texture, station, damage) = match station_map.get(&tile_pos) {
+            Some(station_entity) => {
+                let Ok((station, damage)) = station_q.get(station_entity) else {
+                    continue;
+                };
+                (station_entity, None, Some(station), damage)
+            }
+            None => {
+                let Some(tile_entity) = tile_storage.get(&tile_pos) else {
+                    continue;
+                };
+                let Ok((tile_texture, damage)) = tile_q.get(tile_entity) else {
+                    continue;
+                };
+                (tile_entity, Some(*tile_texture), None, damage)
+            }
+        };
+        let tile_type = tile_texture.and_then(|texture| tile_types.by_texture_index(texture));
+        let hardness = match (tile_type, station) {
+            (Some(tile_type), _) => tile_type.get_hardness(),
+            (None, Some(_)) => STATION_HARDNESS,
+            (None, None) => HIT_DAMAGE,
+        };
+        let tile_center =
+            tile_pos.center_in_world(grid_size, map_type) + map_transform.translation.truncate();
+        let bar_pos = Vec3::new(
+            tile_center.x - TILE_SIZE.x / 2.,
+            tile_center.y - TILE_SIZE.y / 2. + 2.,
+            Z_MINING_PROGRESS,
+        );
+
+        // a too weak pickaxe does no damage, a full red bar tells why
+        if tile_type.map_or(0, |tile_type| tile_type.get_min_power()) > power {
+            miner.swing_timer.reset();
+            progress_transform.translation = bar_pos;
+            progress_transform.scale.x = 1.;
+            progress_sprite.color = TOO_WEAK_COLOR;
+            progress_visibility.is_visible = true;
+            refused_transform.translation = Vec3::new(
+                tile_center.x,
+                tile_center.y + TILE_SIZE.y / 2. + 2.,
+                Z_MINING_PROGRESS,
+            );
+            refused_visibility.is_visible = true;
+            continue;
+        }
+
+        let dealt = damage.map_or(0., |damage| damage.get_amount()) + swings as f32 * HIT_DAMAGE;
+
+        if dealt >= hardness {
+            if let Some(station) = station {
+                remove_station(
+                    &mut commands,
+                    &mut station_map,
+                    tile_entity,
+                    station,
+                    &mut destroyed_stations,
+                );
             }
+            if let Some(tile_texture) = tile_texture {
+                remove_foreground_tile(
+                    &mut commands,
+                    &mut tile_storage,
+                    tile_pos,
+                    tile_texture,
+                    &mut tile_changes,
+                    &mut destroyed_tiles,
+                );
+            }
+            continue;
         }
+
+        if swings > 0 {
+            commands.entity(tile_entity).insert(TileDamage::new(dealt));
+        }
+
+        // the ongoing swing counts too, so that the bar fills smoothly
+        let progress = (dealt + miner.swing_timer.percent() * HIT_DAMAGE) / hardness;
+        progress_transform.translation = bar_pos;
+        progress_transform.scale.x = progress.min(1.);
+        progress_sprite.color = PROGRESS_COLOR;
+        progress_visibility.is_visible = true;
     }
 }
@@ -0,0 +1,247 @@
+use std::collections::HashSet;
+
+use bevy::prelude::*;
+use bevy_ecs_tilemap::prelude::*;
+use bevy_rapier2d::prelude::*;
+use rand::prelude::*;
+
+use crate::constants::{
+    collision_groups::ITEM_COLLIDE_WITH_MAP,
+    depth::Z_ITEMS,
+    items::*,
+    map::{GRID_SIZE, MAP_TYPE},
+    world::GRAVITY,
+};
+use crate::game_state::WorldEntity;
+use crate::inventory::Inventory;
+use crate::item::{ItemId, ItemRegistry, ItemSprites};
+use crate::map::TileDestroyed;
+use crate::player::Player;
+use crate::stations::StationDestroyed;
+use crate::tile::TileCollection;
+use crate::world_settings::WorldSettings;
+
+#[derive(Component)]
+pub struct ItemDrop {
+    item: ItemId,
+    count: u32,
+    lifetime: Timer,
+}
+
+// sent when the player picks up a drop
+pub struct ItemPickedUp {
+    pub item: ItemId,
+    pub count: u32,
+}
+
+impl ItemDrop {
+    pub fn item(&self) -> &ItemId {
+        &self.item
+    }
+
+    pub fn count(&self) -> u32 {
+        self.count
+    }
+
+    // seconds until the drop disappears
+    pub fn time_left(&self) -> f32 {
+        (self.lifetime.duration() - self.lifetime.elapsed()).as_secs_f32()
+    }
+
+    fn new(item: ItemId, count: u32, lifetime: f32) -> Self {
+        Self {
+            item,
+            count,
+            lifetime: Timer::from_seconds(lifetime, TimerMode::Once),
+        }
+    }
+}
+
+pub fn spawn_item_drop(
+    commands: &mut Commands,
+    registry: &ItemRegistry,
+    item_sprites: &ItemSprites,
+    item: ItemId,
+    count: u32,
+    position: Vec2,
+    lifetime: f32,
+) {
+    let Some(item_def) = registry.get(&item) else {
+        return;
+    };
+    let pop = Vec2::new(
+        thread_rng().gen_range(-DROP_POP_SPEED..DROP_POP_SPEED) / 2.,
+        DROP_POP_SPEED,
+    );
+
+    commands
+        .spawn(SpriteSheetBundle {
+            sprite: TextureAtlasSprite {
+                index: item_def.icon,
+                custom_size: Some(Vec2::splat(DROP_SIZE)),
+                ..default()
+            },
+            texture_atlas: item_sprites.0.clone(),
+            transform: Transform::from_translation(position.extend(Z_ITEMS)),
+            ..default()
+        })
+        .insert(Name::new(format!("ItemDrop({})", item_def.name)))
+        .insert(WorldEntity)
+        .insert(ItemDrop::new(item, count, lifetime))
+        .insert(RigidBody::Dynamic)
+        .insert(LockedAxes::ROTATION_LOCKED)
+        .insert(Collider::cuboid(DROP_SIZE / 2., DROP_SIZE / 2.))
+        .insert(ITEM_COLLIDE_WITH_MAP)
+        .insert(GravityScale(GRAVITY))
+        .insert(Velocity::linear(pop));
+}
+
+pub fn spawn_tile_drops(
+    mut commands: Commands,
+    mut destroyed_tiles: EventReader<TileDestroyed>,
+    tile_types: Res<TileCollection>,
+    registry: Res<ItemRegistry>,
+    item_sprites: Res<ItemSprites>,
+    settings: Res<WorldSettings>,
+) {
+    for destroyed in destroyed_tiles.iter() {
+        let Some(item) = tile_types
+            .by_texture_index(destroyed.texture_index)
+            .and_then(|tile_type| tile_type.get_drop())
+        else {
+            continue;
+        };
+        let position =
+            destroyed.tile_pos.center_in_world(&GRID_SIZE, &MAP_TYPE) + settings.map_transform();
+        spawn_item_drop(
+            &mut commands,
+            &registry,
+            &item_sprites,
+            item.clone(),
+            1,
+            position,
+            settings.drop_lifetime as f32,
+        );
+    }
+}
+
+pub fn spawn_station_drops(
+    mut commands: Commands,
+    mut destroyed_stations: EventReader<StationDestroyed>,
+    registry: Res<ItemRegistry>,
+    item_sprites: Res<ItemSprites>,
+    settings: Res<WorldSettings>,
+) {
+    for destroyed in destroyed_stations.iter() {
+        let position =
+            destroyed.origin.center_in_world(&GRID_SIZE, &MAP_TYPE) + settings.map_transform();
+        spawn_item_drop(
+            &mut commands,
+            &registry,
+            &item_sprites,
+            destroyed.item.clone(),
+            1,
+            position,
+            settings.drop_lifetime as f32,
+        );
+    }
+}
+
+// identical drops laying next to each other become one bigger drop
+pub fn merge_item_drops(
+    mut commands: Commands,
+    mut drops_q: Query<(Entity, &Transform, &mut ItemDrop)>,
+) {
+    let drops: Vec<(Entity, Vec2, ItemId)> = drops_q
+        .iter()
+        .map(|(entity, transform, drop)| {
+            (entity, transform.translation.truncate(), drop.item.clone())
+        })
+        .collect();
+
+    let mut merged = HashSet::new();
+    for (i, (keeper, keeper_pos, item)) in drops.iter().enumerate() {
+        if merged.contains(keeper) {
+            continue;
+        }
+
+        for (other, other_pos, other_item) in drops.iter().skip(i + 1) {
+            if merged.contains(other)
+                || other_item != item
+                || keeper_pos.distance(*other_pos) > DROP_MERGE_RADIUS
+            {
+                continue;
+            }
+
+            let Ok((_, _, other_drop)) = drops_q.get(*other) else {
+                continue;
+            };
+            let count = other_drop.count;
+            if let Ok((_, _, mut keeper_drop)) = drops_q.get_mut(*keeper) {
+                keeper_drop.count += count;
+                keeper_drop.lifetime.reset();
+                merged.insert(*other);
+                commands.entity(*other).despawn_recursive();
+            }
+        }
+    }
+}
+
+// drops in the pickup radius fly towards the player, unless they do not fit into the inventory
+pub fn attract_item_drops(
+    registry: Res<ItemRegistry>,
+    player_q: Query<(&Transform, &Inventory), With<Player>>,
+    mut drops_q: Query<(&Transform, &ItemDrop, &mut Velocity)>,
+) {
+    let (player_transform, inventory) = player_q.single();
+    let player_pos = player_transform.translation.truncate();
+    for (transform, drop, mut velocity) in drops_q.iter_mut() {
+        let to_player = player_pos - transform.translation.truncate();
+        if to_player.length() < PICKUP_RADIUS && inventory.room_for(&registry, &drop.item) > 0 {
+            velocity.linvel = to_player.normalize_or_zero() * DROP_MAGNET_SPEED;
+        }
+    }
+}
+
+// whatever does not fit into the inventory stays on the ground
+pub fn pick_up_item_drops(
+    mut commands: Commands,
+    registry: Res<ItemRegistry>,
+    mut player_q: Query<(&Transform, &mut Inventory), With<Player>>,
+    mut drops_q: Query<(Entity, &Transform, &mut ItemDrop), Without<Player>>,
+    mut picked_up: EventWriter<ItemPickedUp>,
+) {
+    let (player_transform, mut inventory) = player_q.single_mut();
+    let player_pos = player_transform.translation.truncate();
+    for (drop_entity, transform, mut drop) in drops_q.iter_mut() {
+        if player_pos.distance(transform.translation.truncate()) >= PICKUP_DISTANCE {
+            continue;
+        }
+
+        let left = inventory.add(&registry, &drop.item, drop.count);
+        if left < drop.count {
+            picked_up.send(ItemPickedUp {
+                item: drop.item.clone(),
+                count: drop.count - left,
+            });
+        }
+
+        if left == 0 {
+            commands.entity(drop_entity).despawn_recursive();
+        } else {
+            drop.count = left;
+        }
+    }
+}
+
+pub fn despawn_expired_drops(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut drops_q: Query<(Entity, &mut ItemDrop)>,
+) {
+    for (drop_entity, mut drop) in drops_q.iter_mut() {
+        if drop.lifetime.tick(time.delta()).finished() {
+            commands.entity(drop_entity).despawn_recursive();
+        }
+    }
+}
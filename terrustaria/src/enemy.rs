@@ -0,0 +1,321 @@
+use std::time::Duration;
+
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+use serde::Deserialize;
+
+use crate::constants::{
+    collision_groups::ENEMY_COLLIDE_WITH_MAP_PLAYER_AND_WEAPONS, depth::Z_ENEMIES, enemies::*,
+    world::GRAVITY,
+};
+use crate::drops::spawn_item_drop;
+use crate::game_state::WorldEntity;
+use crate::health::{DamageEvent, DamageSource, Health};
+use crate::item::{ItemId, ItemRegistry, ItemSprites};
+use crate::player::{Jumper, Player};
+use crate::world_settings::WorldSettings;
+
+// the enemies the spawn rules can name
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+pub enum EnemyKind {
+    Slime,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SlimeState {
+    // sits on the ground until it is rested enough to hop again
+    Resting,
+    Airborne,
+}
+
+// the hop a slime decided on, the sign of the speed is its direction
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Hop {
+    pub horizontal_speed: f32,
+}
+
+#[derive(Component)]
+pub struct Slime {
+    state: SlimeState,
+    rest: Timer,
+    attack_cooldown: Timer,
+    // aimless hops go back and forth
+    wander_direction: f32,
+}
+
+// dropped when the enemy is killed
+#[derive(Component)]
+pub struct Loot {
+    pub item: ItemId,
+    pub count: u32,
+}
+
+// damage dealt by touching the enemy
+#[derive(Component)]
+pub struct ContactDamage(pub f32);
+
+impl Default for Slime {
+    fn default() -> Self {
+        let mut attack_cooldown = Timer::from_seconds(SLIME_ATTACK_COOLDOWN, TimerMode::Once);
+        attack_cooldown.tick(attack_cooldown.duration());
+        Self {
+            state: SlimeState::Resting,
+            rest: Timer::from_seconds(SLIME_REST_TIME, TimerMode::Once),
+            attack_cooldown,
+            wander_direction: 1.,
+        }
+    }
+}
+
+impl Slime {
+    // one step of the state machine, it reads nothing but its arguments,
+    // so the same inputs always lead to the same hops
+    pub fn think(&mut self, delta: Duration, grounded: bool, to_player: Vec2) -> Option<Hop> {
+        match self.state {
+            SlimeState::Airborne => {
+                if grounded {
+                    self.state = SlimeState::Resting;
+                    self.rest.reset();
+                }
+                None
+            }
+            SlimeState::Resting => {
+                if !grounded || !self.rest.tick(delta).finished() {
+                    return None;
+                }
+                self.state = SlimeState::Airborne;
+                if to_player.length() <= SLIME_AGGRO_RANGE {
+                    Some(Hop {
+                        horizontal_speed: to_player.x.signum() * SLIME_CHASE_SPEED,
+                    })
+                } else {
+                    self.wander_direction = -self.wander_direction;
+                    Some(Hop {
+                        horizontal_speed: self.wander_direction * SLIME_WANDER_SPEED,
+                    })
+                }
+            }
+        }
+    }
+}
+
+impl EnemyKind {
+    pub fn spawn(
+        self,
+        commands: &mut Commands,
+        asset_server: &AssetServer,
+        position: Vec2,
+        strength: f32,
+    ) -> Entity {
+        match self {
+            EnemyKind::Slime => spawn_slime(commands, asset_server, position, strength),
+        }
+    }
+}
+
+pub fn spawn_slime(
+    commands: &mut Commands,
+    asset_server: &AssetServer,
+    position: Vec2,
+    strength: f32,
+) -> Entity {
+    commands
+        .spawn(SpriteBundle {
+            texture: asset_server.load("slime.png"),
+            transform: Transform::from_translation(position.extend(Z_ENEMIES)),
+            ..default()
+        })
+        .insert(Slime::default())
+        .insert(Jumper::new(SLIME_JUMP_POWER))
+        .insert(Health::new(SLIME_HEALTH * strength))
+        .insert(ContactDamage(SLIME_DAMAGE * strength))
+        .insert(Loot {
+            item: ItemId::new(SLIME_LOOT.0),
+            count: SLIME_LOOT.1,
+        })
+        .insert(Name::new("Slime"))
+        .insert(WorldEntity)
+        .insert(RigidBody::Dynamic)
+        .insert(LockedAxes::ROTATION_LOCKED)
+        .insert(Collider::cuboid(SLIME_HALF_SIZE.0, SLIME_HALF_SIZE.1))
+        .insert(ENEMY_COLLIDE_WITH_MAP_PLAYER_AND_WEAPONS)
+        .insert(GravityScale(GRAVITY))
+        .insert(Velocity::zero())
+        .id()
+}
+
+pub fn slime_ai(
+    time: Res<Time>,
+    player_q: Query<&Transform, (With<Player>, Without<Slime>)>,
+    mut slime_q: Query<(&Transform, &mut Slime, &mut Jumper, &mut Velocity)>,
+) {
+    let Ok(player_transform) = player_q.get_single() else {
+        return;
+    };
+    let player_pos = player_transform.translation.truncate();
+    for (transform, mut slime, mut jumper, mut velocity) in slime_q.iter_mut() {
+        let to_player = player_pos - transform.translation.truncate();
+        if let Some(hop) = slime.think(time.delta(), !jumper.is_jumping(), to_player) {
+            jumper.jump(&mut velocity, hop.horizontal_speed);
+        }
+    }
+}
+
+// touching a slime hurts and pushes the player away from it
+pub fn slime_contact_damage(
+    time: Res<Time>,
+    rapier_context: Res<RapierContext>,
+    player_q: Query<(Entity, &Transform), (With<Player>, Without<Slime>)>,
+    mut slime_q: Query<(Entity, &Transform, &mut Slime, &ContactDamage)>,
+    mut damage_events: EventWriter<DamageEvent>,
+) {
+    let Ok((player, player_transform)) = player_q.get_single() else {
+        return;
+    };
+    for (entity, transform, mut slime, damage) in slime_q.iter_mut() {
+        if !slime.attack_cooldown.tick(time.delta()).finished() {
+            continue;
+        }
+        let touching = rapier_context
+            .contact_pair(entity, player)
+            .map_or(false, |pair| pair.has_any_active_contacts());
+        if !touching {
+            continue;
+        }
+        slime.attack_cooldown.reset();
+        let away = (player_transform.translation.x - transform.translation.x).signum();
+        damage_events.send(DamageEvent {
+            target: player,
+            amount: damage.0,
+            source: DamageSource::Slime,
+            knockback: Vec2::new(away * SLIME_KNOCKBACK.0, SLIME_KNOCKBACK.1),
+            critical: false,
+        });
+    }
+}
+
+pub fn kill_enemies(
+    mut commands: Commands,
+    registry: Res<ItemRegistry>,
+    item_sprites: Res<ItemSprites>,
+    settings: Res<WorldSettings>,
+    enemy_q: Query<(Entity, &Transform, &Health, &Loot)>,
+) {
+    for (entity, transform, health, loot) in enemy_q.iter() {
+        if !health.is_dead() {
+            continue;
+        }
+        spawn_item_drop(
+            &mut commands,
+            &registry,
+            &item_sprites,
+            loot.item.clone(),
+            loot.count,
+            transform.translation.truncate(),
+            settings.drop_lifetime as f32,
+        );
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rest_time() -> Duration {
+        Duration::from_secs_f32(SLIME_REST_TIME)
+    }
+
+    fn far_away() -> Vec2 {
+        Vec2::new(SLIME_AGGRO_RANGE * 2., 0.)
+    }
+
+    #[test]
+    fn resting_slime_waits() {
+        let mut slime = Slime::default();
+        assert_eq!(slime.think(rest_time() / 2, true, Vec2::X), None);
+        assert_eq!(slime.state, SlimeState::Resting);
+        // the rest only counts on the ground
+        assert_eq!(slime.think(rest_time(), false, Vec2::X), None);
+        assert_eq!(slime.state, SlimeState::Resting);
+    }
+
+    #[test]
+    fn slime_hops_toward_player_in_range() {
+        let mut slime = Slime::default();
+        let hop = slime.think(rest_time(), true, Vec2::new(-50., 10.));
+        assert_eq!(
+            hop,
+            Some(Hop {
+                horizontal_speed: -SLIME_CHASE_SPEED
+            })
+        );
+        assert_eq!(slime.state, SlimeState::Airborne);
+    }
+
+    #[test]
+    fn slime_wanders_out_of_range() {
+        let mut slime = Slime::default();
+        let first = slime.think(rest_time(), true, far_away());
+        slime.think(Duration::ZERO, true, far_away());
+        let second = slime.think(rest_time(), true, far_away());
+        assert_eq!(
+            first,
+            Some(Hop {
+                horizontal_speed: -SLIME_WANDER_SPEED
+            })
+        );
+        assert_eq!(
+            second,
+            Some(Hop {
+                horizontal_speed: SLIME_WANDER_SPEED
+            })
+        );
+    }
+
+    #[test]
+    fn slime_rests_again_after_landing() {
+        let mut slime = Slime::default();
+        slime.think(rest_time(), true, Vec2::X);
+        assert_eq!(slime.think(rest_time(), false, Vec2::X), None);
+        assert_eq!(slime.state, SlimeState::Airborne);
+
+        assert_eq!(slime.think(rest_time(), true, Vec2::X), None);
+        assert_eq!(slime.state, SlimeState::Resting);
+        assert_eq!(slime.think(rest_time() / 2, true, Vec2::X), None);
+        assert!(slime.think(rest_time() / 2, true, Vec2::X).is_some());
+    }
+
+    #[test]
+    fn slime_ai_makes_the_jumper_hop() {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins).add_system(slime_ai);
+        app.world
+            .spawn((Player::default(), Transform::from_xyz(100., 20., 0.)));
+        // rested already, the first frame has no time passing
+        let mut slime = Slime::default();
+        slime.rest.tick(rest_time());
+        let slime_entity = app
+            .world
+            .spawn((
+                Transform::default(),
+                slime,
+                Jumper::new(SLIME_JUMP_POWER),
+                Velocity::zero(),
+            ))
+            .id();
+
+        app.update();
+
+        let velocity = app.world.get::<Velocity>(slime_entity).unwrap();
+        assert_eq!(
+            velocity.linvel,
+            Vec2::new(SLIME_CHASE_SPEED, SLIME_JUMP_POWER)
+        );
+        assert!(app.world.get::<Jumper>(slime_entity).unwrap().is_jumping());
+        assert_eq!(
+            app.world.get::<Slime>(slime_entity).unwrap().state,
+            SlimeState::Airborne
+        );
+    }
+}
@@ -0,0 +1,71 @@
+use bevy::prelude::*;
+use bevy_ecs_tilemap::prelude::*;
+use bevy_rapier2d::prelude::RapierConfiguration;
+
+use crate::stations::NearbyStations;
+
+// Paused is pushed on top of InGame, so that the world stays as it is underneath,
+// Settings is pushed on top of Paused or MainMenu and Dead on top of InGame in the same way
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum AppState {
+    MainMenu,
+    WorldSelect,
+    Loading,
+    InGame,
+    Paused,
+    Settings,
+    Dead,
+}
+
+// everything spawned for a world, tiles are found by their TilemapId instead
+#[derive(Component)]
+pub struct WorldEntity;
+
+// the save is read on entering Loading, the world is spawned from it on entering InGame
+pub fn finish_loading(mut state: ResMut<State<AppState>>) {
+    if let Err(err) = state.set(AppState::InGame) {
+        warn!("cannot enter the game: {err:?}");
+    }
+}
+
+// escape or start opens the pause menu and goes back by one screen in it
+pub fn toggle_pause(
+    keyboard_input: Res<Input<KeyCode>>,
+    gamepads: Res<Gamepads>,
+    gamepad_buttons: Res<Input<GamepadButton>>,
+    mut state: ResMut<State<AppState>>,
+) {
+    let start_pressed = gamepads.iter().any(|gamepad| {
+        gamepad_buttons.just_pressed(GamepadButton::new(gamepad, GamepadButtonType::Start))
+    });
+    if !keyboard_input.just_pressed(KeyCode::Escape) && !start_pressed {
+        return;
+    }
+    let result = match state.current() {
+        AppState::InGame => state.push(AppState::Paused),
+        AppState::Paused | AppState::Settings => state.pop(),
+        _ => return,
+    };
+    if let Err(err) = result {
+        warn!("cannot toggle the pause: {err:?}");
+    }
+}
+
+pub fn pause_physics(mut rapier_config: ResMut<RapierConfiguration>) {
+    rapier_config.physics_pipeline_active = false;
+}
+
+pub fn resume_physics(mut rapier_config: ResMut<RapierConfiguration>) {
+    rapier_config.physics_pipeline_active = true;
+}
+
+// runs on entering the main menu, so that the next world starts from nothing
+pub fn despawn_world(
+    mut commands: Commands,
+    world_q: Query<Entity, Or<(With<WorldEntity>, With<TilemapId>)>>,
+) {
+    for entity in world_q.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    commands.insert_resource(NearbyStations::default());
+}
@@ -0,0 +1,281 @@
+use bevy::input::mouse::MouseMotion;
+use bevy::prelude::*;
+
+use crate::actions::{Action, Actions};
+use crate::constants::{depth::Z_GAMEPAD_CURSOR, gamepad::*};
+use crate::cursor::CursorPos;
+use crate::game_state::{AppState, WorldEntity};
+use crate::player::MainCamera;
+
+// aiming with the right stick instead of the mouse, the offset follows the player around
+#[derive(Resource, Default)]
+pub struct GamepadCursor {
+    active: bool,
+    offset: Vec2,
+}
+
+#[derive(Component)]
+pub struct GamepadCursorSprite;
+
+// button of a menu focused with the d-pad or the left stick
+#[derive(Resource, Default)]
+pub struct GamepadFocus {
+    entity: Option<Entity>,
+    // the stick has to return to the center before it moves the focus again
+    stick_held: bool,
+}
+
+impl GamepadCursor {
+    // None while the mouse is used
+    pub fn position(&self, player_pos: Vec2) -> Option<Vec2> {
+        self.active.then_some(player_pos + self.offset)
+    }
+}
+
+// the first of the connected gamepads with the stick out of its dead zone
+fn stick(
+    gamepads: &Gamepads,
+    axes: &Axis<GamepadAxis>,
+    x: GamepadAxisType,
+    y: GamepadAxisType,
+) -> Vec2 {
+    gamepads
+        .iter()
+        .map(|gamepad| {
+            Vec2::new(
+                axes.get(GamepadAxis::new(gamepad, x)).unwrap_or(0.),
+                axes.get(GamepadAxis::new(gamepad, y)).unwrap_or(0.),
+            )
+        })
+        .find(|value| value.length() > STICK_DEADZONE)
+        .unwrap_or(Vec2::ZERO)
+}
+
+fn just_pressed(
+    gamepads: &Gamepads,
+    buttons: &Input<GamepadButton>,
+    button_type: GamepadButtonType,
+) -> bool {
+    gamepads
+        .iter()
+        .any(|gamepad| buttons.just_pressed(GamepadButton::new(gamepad, button_type)))
+}
+
+// the one axis the stick is tilted the most along
+fn dominant_direction(value: Vec2) -> Vec2 {
+    if value.x.abs() > value.y.abs() {
+        Vec2::new(value.x.signum(), 0.)
+    } else {
+        Vec2::new(0., value.y.signum())
+    }
+}
+
+pub fn spawn_gamepad_cursor(mut commands: Commands, mut cursor: ResMut<GamepadCursor>) {
+    *cursor = GamepadCursor::default();
+    commands
+        .spawn(SpriteBundle {
+            sprite: Sprite {
+                color: CURSOR_COLOR,
+                custom_size: Some(Vec2::splat(CURSOR_SIZE)),
+                ..default()
+            },
+            visibility: Visibility { is_visible: false },
+            ..default()
+        })
+        .insert(GamepadCursorSprite)
+        .insert(Name::new("GamepadCursor"))
+        .insert(WorldEntity);
+}
+
+// the right stick takes over the aiming, moving the mouse gives it back
+pub fn move_gamepad_cursor(
+    time: Res<Time>,
+    gamepads: Res<Gamepads>,
+    axes: Res<Axis<GamepadAxis>>,
+    mut mouse_motion: EventReader<MouseMotion>,
+    mut cursor: ResMut<GamepadCursor>,
+) {
+    let value = stick(
+        &gamepads,
+        &axes,
+        GamepadAxisType::RightStickX,
+        GamepadAxisType::RightStickY,
+    );
+    let mouse_moved = mouse_motion.iter().count() > 0;
+    if value != Vec2::ZERO {
+        cursor.active = true;
+        cursor.offset = (cursor.offset + value * CURSOR_SPEED * time.delta_seconds())
+            .clamp_length_max(CURSOR_RANGE);
+    } else if mouse_moved && cursor.active {
+        cursor.active = false;
+    }
+}
+
+// the mouse pointer is hidden behind the world, so the virtual one is drawn instead
+pub fn update_gamepad_cursor_sprite(
+    cursor: Res<GamepadCursor>,
+    cursor_pos: Res<CursorPos>,
+    mut sprite_q: Query<(&mut Transform, &mut Visibility), With<GamepadCursorSprite>>,
+) {
+    for (mut transform, mut visibility) in sprite_q.iter_mut() {
+        visibility.is_visible = cursor.active;
+        transform.translation = cursor_pos.0.truncate().extend(Z_GAMEPAD_CURSOR);
+    }
+}
+
+// buttons of the hud under the virtual cursor are hovered, and clicked by using an item
+pub fn point_ui_with_gamepad_cursor(
+    cursor: Res<GamepadCursor>,
+    cursor_pos: Res<CursorPos>,
+    camera_q: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
+    actions: Actions,
+    mut button_q: Query<
+        (
+            &Node,
+            &GlobalTransform,
+            &ComputedVisibility,
+            &mut Interaction,
+        ),
+        With<Button>,
+    >,
+) {
+    if !cursor.active {
+        return;
+    }
+    let Ok((camera, camera_transform)) = camera_q.get_single() else {
+        return;
+    };
+    let Some(screen_pos) = camera.world_to_viewport(camera_transform, cursor_pos.0) else {
+        return;
+    };
+    let clicked = actions.just_pressed(Action::UseItem);
+
+    for (node, transform, visibility, mut interaction) in button_q.iter_mut() {
+        let center = transform.translation().truncate();
+        let inside = (screen_pos - center).abs().cmple(node.size() / 2.).all();
+        if !visibility.is_visible() || !inside {
+            continue;
+        }
+        let pointed = if clicked {
+            Interaction::Clicked
+        } else {
+            Interaction::Hovered
+        };
+        // the ui resets hovered buttons not under the mouse every frame
+        if *interaction != pointed {
+            *interaction = pointed;
+        }
+    }
+}
+
+// the menus are walked through with the d-pad or the left stick and clicked with the south button
+pub fn navigate_menus_with_gamepad(
+    state: Res<State<AppState>>,
+    gamepads: Res<Gamepads>,
+    buttons: Res<Input<GamepadButton>>,
+    axes: Res<Axis<GamepadAxis>>,
+    mut mouse_motion: EventReader<MouseMotion>,
+    mut focus: ResMut<GamepadFocus>,
+    mut button_q: Query<
+        (
+            Entity,
+            &Node,
+            &GlobalTransform,
+            &ComputedVisibility,
+            &mut Interaction,
+        ),
+        With<Button>,
+    >,
+) {
+    // the world has the virtual cursor instead
+    if *state.current() == AppState::InGame {
+        return;
+    }
+    if mouse_motion.iter().count() > 0 {
+        if let Some(entity) = focus.entity.take() {
+            if let Ok((.., mut interaction)) = button_q.get_mut(entity) {
+                *interaction = Interaction::None;
+            }
+        }
+        return;
+    }
+
+    let value = stick(
+        &gamepads,
+        &axes,
+        GamepadAxisType::LeftStickX,
+        GamepadAxisType::LeftStickY,
+    );
+    let mut direction = None;
+    if value != Vec2::ZERO && !focus.stick_held {
+        direction = Some(dominant_direction(value));
+    }
+    focus.stick_held = value != Vec2::ZERO;
+    for (button_type, dpad_direction) in [
+        (GamepadButtonType::DPadUp, Vec2::Y),
+        (GamepadButtonType::DPadDown, Vec2::NEG_Y),
+        (GamepadButtonType::DPadLeft, Vec2::NEG_X),
+        (GamepadButtonType::DPadRight, Vec2::X),
+    ] {
+        if just_pressed(&gamepads, &buttons, button_type) {
+            direction = Some(dpad_direction);
+        }
+    }
+    let confirm = just_pressed(&gamepads, &buttons, GamepadButtonType::South);
+
+    // ui positions grow upwards, like the ones of the window
+    let visible: Vec<(Entity, Vec2)> = button_q
+        .iter()
+        .filter(|(_, node, _, visibility, _)| visibility.is_visible() && node.size() != Vec2::ZERO)
+        .map(|(entity, _, transform, ..)| (entity, transform.translation().truncate()))
+        .collect();
+    // the focused button is gone once its screen is spawned again
+    let current = focus
+        .entity
+        .and_then(|entity| visible.iter().find(|(visible, _)| *visible == entity))
+        .copied();
+
+    let next = match (current, direction) {
+        (None, None) if !confirm => return,
+        // the first input only shows where the focus starts
+        (None, _) => visible
+            .iter()
+            .max_by(|(_, a), (_, b)| a.y.total_cmp(&b.y).then(b.x.total_cmp(&a.x)))
+            .map(|(entity, _)| *entity),
+        (Some((entity, from)), Some(direction)) => visible
+            .iter()
+            .filter(|(_, to)| (*to - from).dot(direction) > 0.)
+            .min_by(|(_, a), (_, b)| {
+                // buttons out of the line count as further away
+                let score = |to: &Vec2| {
+                    let diff = *to - from;
+                    let along = diff.dot(direction);
+                    along + (diff - direction * along).length() * 2.
+                };
+                score(a).total_cmp(&score(b))
+            })
+            .map_or(Some(entity), |(entity, _)| Some(*entity)),
+        (Some((entity, _)), None) => Some(entity),
+    };
+
+    if next != focus.entity {
+        if let Some(previous) = focus.entity {
+            if let Ok((.., mut interaction)) = button_q.get_mut(previous) {
+                *interaction = Interaction::None;
+            }
+        }
+        focus.entity = next;
+    }
+    let Some(focused) = focus.entity else {
+        return;
+    };
+    let Ok((.., mut interaction)) = button_q.get_mut(focused) else {
+        return;
+    };
+    if confirm && current.map(|(entity, _)| entity) == Some(focused) {
+        *interaction = Interaction::Clicked;
+    } else if *interaction != Interaction::Hovered {
+        // the ui resets hovered buttons not under the mouse every frame
+        *interaction = Interaction::Hovered;
+    }
+}
@@ -0,0 +1,338 @@
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+
+use crate::constants::{combat::*, depth::Z_DAMAGE_NUMBERS, health::*, hud::*};
+use crate::game_state::WorldEntity;
+use crate::hotbar::HudRoot;
+use crate::player::Player;
+
+#[derive(Component)]
+pub struct Health {
+    current: f32,
+    max: f32,
+    // runs again every time damage is taken, the health regenerates once it finished
+    regen_delay: Timer,
+    last_damage: Option<DamageSource>,
+}
+
+// tracks the fastest downward speed since the entity last stood or went up
+#[derive(Component, Default)]
+pub struct FallTracker {
+    peak_speed: f32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DamageSource {
+    Fall,
+    Slime,
+    Melee,
+    Projectile,
+}
+
+impl DamageSource {
+    // shown on the death screen
+    pub fn cause(self) -> &'static str {
+        match self {
+            DamageSource::Fall => "fell from too high",
+            DamageSource::Slime => "were slain by a slime",
+            DamageSource::Melee => "were cut down",
+            DamageSource::Projectile => "were shot",
+        }
+    }
+}
+
+pub struct DamageEvent {
+    pub target: Entity,
+    pub amount: f32,
+    pub source: DamageSource,
+    // replaces the velocity of the target, zero leaves it alone
+    pub knockback: Vec2,
+    pub critical: bool,
+}
+
+// rises over the damaged entity and fades away
+#[derive(Component)]
+pub struct DamageNumber {
+    lifetime: Timer,
+}
+
+#[derive(Component)]
+pub struct HealthBarFill;
+#[derive(Component)]
+pub struct HealthBarText;
+
+impl Health {
+    pub fn new(max: f32) -> Self {
+        Self::with_current(max, max)
+    }
+
+    pub fn with_current(current: f32, max: f32) -> Self {
+        let mut regen_delay = Timer::from_seconds(REGEN_DELAY, TimerMode::Once);
+        regen_delay.tick(regen_delay.duration());
+        Self {
+            current: current.clamp(0., max),
+            max,
+            regen_delay,
+            last_damage: None,
+        }
+    }
+
+    pub fn current(&self) -> f32 {
+        self.current
+    }
+
+    pub fn max(&self) -> f32 {
+        self.max
+    }
+
+    pub fn fraction(&self) -> f32 {
+        if self.max > 0. {
+            self.current / self.max
+        } else {
+            0.
+        }
+    }
+
+    pub fn is_dead(&self) -> bool {
+        self.current <= 0.
+    }
+
+    // what dealt the latest damage, the killing blow once the entity is dead
+    pub fn last_damage(&self) -> Option<DamageSource> {
+        self.last_damage
+    }
+
+    pub fn revive(&mut self) {
+        *self = Self::new(self.max);
+    }
+
+    fn take(&mut self, amount: f32, source: DamageSource) {
+        self.current = (self.current - amount).max(0.);
+        self.regen_delay.reset();
+        self.last_damage = Some(source);
+    }
+}
+
+pub fn apply_damage(
+    mut damage_events: EventReader<DamageEvent>,
+    mut health_q: Query<(&mut Health, Option<&mut Velocity>)>,
+) {
+    for event in damage_events.iter() {
+        let Ok((mut health, velocity)) = health_q.get_mut(event.target) else {
+            continue;
+        };
+        if health.is_dead() {
+            continue;
+        }
+        health.take(event.amount, event.source);
+        if let Some(mut velocity) = velocity.filter(|_| event.knockback != Vec2::ZERO) {
+            velocity.linvel = event.knockback;
+        }
+        debug!(
+            "{:?} took {} damage from {:?}",
+            event.target, event.amount, event.source
+        );
+    }
+}
+
+pub fn regenerate_health(time: Res<Time>, mut health_q: Query<&mut Health>) {
+    for mut health in health_q.iter_mut() {
+        if health.is_dead() || health.current >= health.max {
+            continue;
+        }
+        health.regen_delay.tick(time.delta());
+        if health.regen_delay.finished() {
+            health.current = (health.current + REGEN_RATE * time.delta_seconds()).min(health.max);
+        }
+    }
+}
+
+// runs after the landings were checked, so that the speed before hitting the ground is used
+pub fn track_fall_speed(mut fall_q: Query<(&Velocity, &mut FallTracker)>) {
+    for (velocity, mut fall) in fall_q.iter_mut() {
+        if velocity.linvel.y >= 0. {
+            fall.peak_speed = 0.;
+        } else {
+            fall.peak_speed = fall.peak_speed.max(-velocity.linvel.y);
+        }
+    }
+}
+
+// the contact counts as a landing when its normal pushes the entity up
+fn landed_on(rapier_context: &RapierContext, entity: Entity, other: Entity) -> bool {
+    let Some(pair) = rapier_context.contact_pair(entity, other) else {
+        return false;
+    };
+    pair.manifolds().any(|manifold| {
+        // the normal points from the first collider of the pair to the second one
+        let normal = if pair.collider1() == entity {
+            -manifold.normal()
+        } else {
+            manifold.normal()
+        };
+        normal.y > LANDING_NORMAL_Y
+    })
+}
+
+pub fn apply_fall_damage(
+    rapier_context: Res<RapierContext>,
+    mut contact_events: EventReader<CollisionEvent>,
+    mut fall_q: Query<(Entity, &mut FallTracker)>,
+    mut damage_events: EventWriter<DamageEvent>,
+) {
+    for contact_event in contact_events.iter() {
+        let CollisionEvent::Started(h1, h2, ..) = contact_event else {
+            continue;
+        };
+        for (entity, mut fall) in fall_q.iter_mut() {
+            let other = if *h1 == entity {
+                *h2
+            } else if *h2 == entity {
+                *h1
+            } else {
+                continue;
+            };
+            if !landed_on(&rapier_context, entity, other) {
+                continue;
+            }
+            let excess = std::mem::take(&mut fall.peak_speed) - SAFE_FALL_SPEED;
+            if excess > 0. {
+                damage_events.send(DamageEvent {
+                    target: entity,
+                    amount: excess * FALL_DAMAGE_PER_SPEED,
+                    source: DamageSource::Fall,
+                    knockback: Vec2::ZERO,
+                    critical: false,
+                });
+            }
+        }
+    }
+}
+
+// the bar sits under the hotbar
+pub fn spawn_health_bar(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    hud_q: Query<Entity, Added<HudRoot>>,
+) {
+    let Ok(hud_root) = hud_q.get_single() else {
+        return;
+    };
+    let y = -SLOT_SIZE - SLOT_GAP - HEALTH_BAR_HEIGHT / 2.;
+    commands.entity(hud_root).with_children(|parent| {
+        parent
+            .spawn(SpriteBundle {
+                sprite: Sprite {
+                    color: HEALTH_BAR_BACKGROUND,
+                    custom_size: Some(Vec2::new(HEALTH_BAR_WIDTH, HEALTH_BAR_HEIGHT)),
+                    ..default()
+                },
+                transform: Transform::from_xyz(HEALTH_BAR_WIDTH / 2., y, 0.),
+                ..default()
+            })
+            .insert(Name::new("HealthBar"))
+            .with_children(|bar| {
+                // anchored on its left side, the width shows the health left
+                bar.spawn(SpriteBundle {
+                    sprite: Sprite {
+                        color: HEALTH_BAR_COLOR,
+                        custom_size: Some(Vec2::new(HEALTH_BAR_WIDTH, HEALTH_BAR_HEIGHT)),
+                        anchor: bevy::sprite::Anchor::CenterLeft,
+                        ..default()
+                    },
+                    transform: Transform::from_xyz(-HEALTH_BAR_WIDTH / 2., 0., 0.01),
+                    ..default()
+                })
+                .insert(HealthBarFill);
+                bar.spawn(Text2dBundle {
+                    text: Text::from_section(
+                        "",
+                        TextStyle {
+                            font: asset_server.load(FONT),
+                            font_size: HEALTH_FONT_SIZE,
+                            color: Color::WHITE,
+                        },
+                    )
+                    .with_alignment(TextAlignment::CENTER),
+                    transform: Transform::from_xyz(0., 0., 0.02),
+                    ..default()
+                })
+                .insert(HealthBarText);
+            });
+    });
+}
+
+pub fn update_health_bar(
+    player_q: Query<&Health, With<Player>>,
+    mut fill_q: Query<&mut Sprite, With<HealthBarFill>>,
+    mut text_q: Query<&mut Text, With<HealthBarText>>,
+) {
+    let Ok(health) = player_q.get_single() else {
+        return;
+    };
+    for mut sprite in fill_q.iter_mut() {
+        sprite.custom_size = Some(Vec2::new(
+            HEALTH_BAR_WIDTH * health.fraction(),
+            HEALTH_BAR_HEIGHT,
+        ));
+    }
+    for mut text in text_q.iter_mut() {
+        text.sections[0].value = format!("{}/{}", health.current.ceil(), health.max);
+    }
+}
+
+pub fn spawn_damage_numbers(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut damage_events: EventReader<DamageEvent>,
+    target_q: Query<&GlobalTransform>,
+) {
+    for event in damage_events.iter() {
+        let Ok(target) = target_q.get(event.target) else {
+            continue;
+        };
+        let color = if event.critical {
+            CRIT_NUMBER_COLOR
+        } else {
+            DAMAGE_NUMBER_COLOR
+        };
+        commands
+            .spawn(Text2dBundle {
+                text: Text::from_section(
+                    format!("{}", event.amount.round()),
+                    TextStyle {
+                        font: asset_server.load(FONT),
+                        font_size: DAMAGE_NUMBER_FONT_SIZE,
+                        color,
+                    },
+                )
+                .with_alignment(TextAlignment::CENTER),
+                transform: Transform::from_translation(
+                    target.translation().truncate().extend(Z_DAMAGE_NUMBERS),
+                ),
+                ..default()
+            })
+            .insert(DamageNumber {
+                lifetime: Timer::from_seconds(DAMAGE_NUMBER_LIFETIME, TimerMode::Once),
+            })
+            .insert(WorldEntity);
+    }
+}
+
+pub fn float_damage_numbers(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut number_q: Query<(Entity, &mut Transform, &mut Text, &mut DamageNumber)>,
+) {
+    for (entity, mut transform, mut text, mut number) in number_q.iter_mut() {
+        if number.lifetime.tick(time.delta()).finished() {
+            commands.entity(entity).despawn_recursive();
+            continue;
+        }
+        transform.translation.y += DAMAGE_NUMBER_RISE_SPEED * time.delta_seconds();
+        text.sections[0]
+            .style
+            .color
+            .set_a(number.lifetime.percent_left());
+    }
+}
@@ -0,0 +1,215 @@
+use bevy::input::mouse::MouseWheel;
+use bevy::prelude::*;
+
+use crate::actions::{Action, Actions};
+use crate::constants::{hud::*, inventory::HOTBAR_SLOTS};
+use crate::inventory::{Inventory, ItemStack};
+use crate::item::{ItemDef, ItemRegistry, ItemSprites};
+use crate::player::{MainCamera, Player};
+
+#[derive(Component, Default)]
+pub struct Hotbar {
+    selected: usize,
+}
+
+// all of the hud is attached to the camera, so that it follows the player
+#[derive(Component)]
+pub struct HudRoot;
+#[derive(Component)]
+pub struct HotbarSlot(usize);
+#[derive(Component)]
+pub struct HotbarIcon(usize);
+#[derive(Component)]
+pub struct HotbarCount(usize);
+
+const SLOT_KEYS: [KeyCode; HOTBAR_SLOTS] = [
+    KeyCode::Key1,
+    KeyCode::Key2,
+    KeyCode::Key3,
+    KeyCode::Key4,
+    KeyCode::Key5,
+    KeyCode::Key6,
+    KeyCode::Key7,
+    KeyCode::Key8,
+    KeyCode::Key9,
+    KeyCode::Key0,
+];
+
+impl Hotbar {
+    pub fn new(selected: usize) -> Self {
+        Self {
+            selected: selected.min(HOTBAR_SLOTS - 1),
+        }
+    }
+
+    pub fn selected(&self) -> usize {
+        self.selected
+    }
+
+    pub fn selected_stack<'a>(&self, inventory: &'a Inventory) -> Option<&'a ItemStack> {
+        inventory.get(self.selected)
+    }
+
+    pub fn selected_item<'a>(
+        &self,
+        inventory: &Inventory,
+        registry: &'a ItemRegistry,
+    ) -> Option<&'a ItemDef> {
+        self.selected_stack(inventory)
+            .and_then(|stack| registry.get(&stack.item))
+    }
+}
+
+pub fn spawn_hotbar(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    item_sprites: Res<ItemSprites>,
+    camera_q: Query<Entity, Added<MainCamera>>,
+) {
+    // the camera is spawned together with the player
+    let Ok(camera) = camera_q.get_single() else {
+        return;
+    };
+    let font = asset_server.load(FONT);
+    let hud_root = commands
+        .spawn(SpatialBundle::default())
+        .insert(HudRoot)
+        .insert(Name::new("Hud"))
+        .with_children(|parent| {
+            for slot in 0..HOTBAR_SLOTS {
+                let x = slot as f32 * (SLOT_SIZE + SLOT_GAP) + SLOT_SIZE / 2.;
+                parent
+                    .spawn(SpriteBundle {
+                        sprite: Sprite {
+                            color: SLOT_COLOR,
+                            custom_size: Some(Vec2::splat(SLOT_SIZE)),
+                            ..default()
+                        },
+                        transform: Transform::from_xyz(x, -SLOT_SIZE / 2., 0.),
+                        ..default()
+                    })
+                    .insert(HotbarSlot(slot))
+                    .insert(Name::new(format!("HotbarSlot({slot})")))
+                    .with_children(|slot_parent| {
+                        slot_parent
+                            .spawn(SpriteSheetBundle {
+                                sprite: TextureAtlasSprite {
+                                    custom_size: Some(Vec2::splat(SLOT_ICON_SIZE)),
+                                    ..default()
+                                },
+                                texture_atlas: item_sprites.0.clone(),
+                                transform: Transform::from_xyz(0., 0., 0.01),
+                                visibility: Visibility { is_visible: false },
+                                ..default()
+                            })
+                            .insert(HotbarIcon(slot));
+                        slot_parent
+                            .spawn(Text2dBundle {
+                                text: Text::from_section(
+                                    "",
+                                    TextStyle {
+                                        font: font.clone(),
+                                        font_size: SLOT_FONT_SIZE,
+                                        color: Color::WHITE,
+                                    },
+                                )
+                                .with_alignment(TextAlignment::BOTTOM_RIGHT),
+                                transform: Transform::from_xyz(
+                                    SLOT_SIZE / 2. - 2.,
+                                    -SLOT_SIZE / 2. + 2.,
+                                    0.02,
+                                ),
+                                ..default()
+                            })
+                            .insert(HotbarCount(slot));
+                    });
+            }
+        })
+        .id();
+
+    commands.entity(camera).add_child(hud_root);
+}
+
+// keeps the hud in the top left corner of the window
+pub fn position_hud(windows: Res<Windows>, mut hud_q: Query<&mut Transform, With<HudRoot>>) {
+    let Some(window) = windows.get_primary() else {
+        return;
+    };
+    for mut transform in hud_q.iter_mut() {
+        transform.translation = Vec3::new(
+            -window.width() / 2. + HUD_MARGIN,
+            window.height() / 2. - HUD_MARGIN,
+            Z_HUD,
+        );
+    }
+}
+
+// number keys pick the slot directly, the mouse wheel and the bumpers cycle through them
+pub fn select_hotbar_slot(
+    keyboard_input: Res<Input<KeyCode>>,
+    actions: Actions,
+    mut wheel_events: EventReader<MouseWheel>,
+    mut hotbar_q: Query<&mut Hotbar, With<Player>>,
+) {
+    let mut hotbar = hotbar_q.single_mut();
+
+    for (slot, key) in SLOT_KEYS.iter().enumerate() {
+        if keyboard_input.just_pressed(*key) {
+            hotbar.selected = slot;
+        }
+    }
+
+    for wheel in wheel_events.iter() {
+        if wheel.y < 0. {
+            hotbar.selected = (hotbar.selected + 1) % HOTBAR_SLOTS;
+        } else if wheel.y > 0. {
+            hotbar.selected = (hotbar.selected + HOTBAR_SLOTS - 1) % HOTBAR_SLOTS;
+        }
+    }
+
+    if actions.just_pressed(Action::NextSlot) {
+        hotbar.selected = (hotbar.selected + 1) % HOTBAR_SLOTS;
+    }
+    if actions.just_pressed(Action::PreviousSlot) {
+        hotbar.selected = (hotbar.selected + HOTBAR_SLOTS - 1) % HOTBAR_SLOTS;
+    }
+}
+
+pub fn update_hotbar(
+    registry: Res<ItemRegistry>,
+    player_q: Query<(&Inventory, &Hotbar), With<Player>>,
+    mut slot_q: Query<(&HotbarSlot, &mut Sprite)>,
+    mut icon_q: Query<(&HotbarIcon, &mut TextureAtlasSprite, &mut Visibility)>,
+    mut count_q: Query<(&HotbarCount, &mut Text)>,
+) {
+    let (inventory, hotbar) = player_q.single();
+
+    for (slot, mut sprite) in slot_q.iter_mut() {
+        sprite.color = if slot.0 == hotbar.selected {
+            SELECTED_SLOT_COLOR
+        } else {
+            SLOT_COLOR
+        };
+    }
+
+    for (icon, mut sprite, mut visibility) in icon_q.iter_mut() {
+        let item_def = inventory
+            .get(icon.0)
+            .and_then(|stack| registry.get(&stack.item));
+        visibility.is_visible = item_def.is_some();
+        if let Some(item_def) = item_def {
+            sprite.index = item_def.icon;
+        }
+    }
+
+    for (count, mut text) in count_q.iter_mut() {
+        let value = match inventory.get(count.0) {
+            Some(stack) if stack.count > 1 => stack.count.to_string(),
+            _ => String::new(),
+        };
+        // text is laid out again whenever it changes
+        if text.sections[0].value != value {
+            text.sections[0].value = value;
+        }
+    }
+}
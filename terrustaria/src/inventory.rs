@@ -0,0 +1,260 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::constants::inventory::INVENTORY_SLOTS;
+use crate::item::{ItemId, ItemRegistry};
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ItemStack {
+    pub item: ItemId,
+    pub count: u32,
+}
+
+#[derive(Component, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Inventory {
+    slots: Vec<Option<ItemStack>>,
+}
+
+impl ItemStack {
+    pub fn new(item: ItemId, count: u32) -> Self {
+        Self { item, count }
+    }
+}
+
+impl Inventory {
+    pub fn new(size: usize) -> Self {
+        Self {
+            slots: vec![None; size],
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn slots(&self) -> &[Option<ItemStack>] {
+        &self.slots
+    }
+
+    pub fn get(&self, slot: usize) -> Option<&ItemStack> {
+        self.slots.get(slot).and_then(|stack| stack.as_ref())
+    }
+
+    pub fn count(&self, item: &ItemId) -> u32 {
+        self.slots
+            .iter()
+            .flatten()
+            .filter(|stack| stack.item == *item)
+            .map(|stack| stack.count)
+            .sum()
+    }
+
+    // how many items of this kind can still be added
+    pub fn room_for(&self, registry: &ItemRegistry, item: &ItemId) -> u32 {
+        let max_stack = registry.max_stack(item);
+        self.slots
+            .iter()
+            .map(|slot| match slot {
+                None => max_stack,
+                Some(stack) if stack.item == *item => max_stack.saturating_sub(stack.count),
+                Some(_) => 0,
+            })
+            .sum()
+    }
+
+    // fills stacks of the same item first, then empty slots,
+    // returns the number of items which did not fit
+    pub fn add(&mut self, registry: &ItemRegistry, item: &ItemId, mut count: u32) -> u32 {
+        let max_stack = registry.max_stack(item);
+
+        for stack in self.slots.iter_mut().flatten() {
+            if count == 0 {
+                return 0;
+            }
+            if stack.item == *item && stack.count < max_stack {
+                let moved = count.min(max_stack - stack.count);
+                stack.count += moved;
+                count -= moved;
+            }
+        }
+
+        for slot in self.slots.iter_mut() {
+            if count == 0 {
+                return 0;
+            }
+            if slot.is_none() {
+                let moved = count.min(max_stack);
+                *slot = Some(ItemStack::new(item.clone(), moved));
+                count -= moved;
+            }
+        }
+
+        count
+    }
+
+    // removes nothing unless all of the items are there,
+    // returns the number of missing items
+    pub fn remove(&mut self, item: &ItemId, mut count: u32) -> u32 {
+        let owned = self.count(item);
+        if owned < count {
+            return count - owned;
+        }
+
+        // the last stacks are emptied first, so the ones in front stay in place
+        for slot in self.slots.iter_mut().rev() {
+            if count == 0 {
+                break;
+            }
+            if let Some(stack) = slot {
+                if stack.item == *item {
+                    let moved = count.min(stack.count);
+                    stack.count -= moved;
+                    count -= moved;
+                    if stack.count == 0 {
+                        *slot = None;
+                    }
+                }
+            }
+        }
+
+        0
+    }
+
+    // takes the whole stack out of the slot
+    #[allow(dead_code)]
+    pub fn take(&mut self, slot: usize) -> Option<ItemStack> {
+        self.slots.get_mut(slot).and_then(|stack| stack.take())
+    }
+
+    // merges partial stacks and orders them by the item name
+    #[allow(dead_code)]
+    pub fn sort(&mut self, registry: &ItemRegistry) {
+        let mut stacks: Vec<ItemStack> = self.slots.iter_mut().filter_map(|s| s.take()).collect();
+        stacks.sort_by(|a, b| {
+            let name = |id: &ItemId| registry.get(id).map(|def| def.name.clone());
+            name(&a.item)
+                .cmp(&name(&b.item))
+                .then(b.count.cmp(&a.count))
+        });
+        for stack in stacks {
+            self.add(registry, &stack.item, stack.count);
+        }
+    }
+
+    // moves every item the other inventory already holds into it,
+    // returns true if anything was moved
+    #[allow(dead_code)]
+    pub fn quick_stack_into(&mut self, registry: &ItemRegistry, other: &mut Inventory) -> bool {
+        let mut moved_any = false;
+        for slot in self.slots.iter_mut() {
+            let Some(stack) = slot else {
+                continue;
+            };
+            if other.count(&stack.item) == 0 {
+                continue;
+            }
+
+            let left = other.add(registry, &stack.item, stack.count);
+            moved_any |= left < stack.count;
+            stack.count = left;
+            if left == 0 {
+                *slot = None;
+            }
+        }
+        moved_any
+    }
+}
+
+impl Default for Inventory {
+    fn default() -> Self {
+        Self::new(INVENTORY_SLOTS)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::item::test_registry;
+
+    fn dirt() -> ItemId {
+        ItemId::new("dirt")
+    }
+
+    fn stone() -> ItemId {
+        ItemId::new("stone")
+    }
+
+    fn stack(item: ItemId, count: u32) -> Option<ItemStack> {
+        Some(ItemStack::new(item, count))
+    }
+
+    #[test]
+    fn add_starts_new_stacks_at_max_stack() {
+        let registry = test_registry();
+        let mut inventory = Inventory::new(3);
+        assert_eq!(inventory.add(&registry, &dirt(), 150), 0);
+        assert_eq!(
+            inventory.slots(),
+            [stack(dirt(), 99), stack(dirt(), 51), None]
+        );
+
+        // the partial stack is filled before the empty slot
+        assert_eq!(inventory.add(&registry, &dirt(), 100), 0);
+        assert_eq!(
+            inventory.slots(),
+            [stack(dirt(), 99), stack(dirt(), 99), stack(dirt(), 52)]
+        );
+
+        assert_eq!(inventory.add(&registry, &dirt(), 100), 53);
+        assert_eq!(inventory.count(&dirt()), 297);
+        assert_eq!(inventory.add(&registry, &stone(), 1), 1);
+    }
+
+    #[test]
+    fn remove_returns_shortfall_without_changes() {
+        let registry = test_registry();
+        let mut inventory = Inventory::new(3);
+        inventory.add(&registry, &dirt(), 150);
+        let before = inventory.clone();
+
+        assert_eq!(inventory.remove(&dirt(), 160), 10);
+        assert_eq!(inventory, before);
+        assert_eq!(inventory.remove(&stone(), 1), 1);
+        assert_eq!(inventory, before);
+
+        // the last stack goes first
+        assert_eq!(inventory.remove(&dirt(), 60), 0);
+        assert_eq!(inventory.slots(), [stack(dirt(), 90), None, None]);
+    }
+
+    #[test]
+    fn sort_merges_stacks_and_orders_them_by_name() {
+        let registry = test_registry();
+        let mut inventory = Inventory {
+            slots: vec![stack(stone(), 5), None, stack(dirt(), 3), stack(stone(), 7)],
+        };
+        inventory.sort(&registry);
+        assert_eq!(
+            inventory.slots(),
+            [stack(dirt(), 3), stack(stone(), 12), None, None]
+        );
+    }
+
+    #[test]
+    fn quick_stack_moves_only_items_the_other_holds() {
+        let registry = test_registry();
+        let mut inventory = Inventory::new(3);
+        inventory.add(&registry, &dirt(), 20);
+        inventory.add(&registry, &stone(), 5);
+        let mut chest = Inventory::new(1);
+        chest.add(&registry, &dirt(), 90);
+
+        assert!(inventory.quick_stack_into(&registry, &mut chest));
+        assert_eq!(chest.slots(), [stack(dirt(), 99)]);
+        assert_eq!(
+            inventory.slots(),
+            [stack(dirt(), 11), stack(stone(), 5), None]
+        );
+
+        // the chest is full now
+        assert!(!inventory.quick_stack_into(&registry, &mut chest));
+        assert_eq!(inventory.count(&dirt()), 11);
+    }
+}
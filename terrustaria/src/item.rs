@@ -0,0 +1,314 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::constants::items::ITEM_ICON_SIZE;
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct ItemId(pub String);
+
+// what happens when the item is used from the hotbar
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+pub enum ItemKind {
+    // can only be crafted with
+    #[default]
+    Material,
+    // placed into the foreground map as the tile of the given name
+    Block {
+        tile: String,
+    },
+    // multi-tile crafting station, the sprite is its frame in stations_strip.png
+    Station {
+        width: u32,
+        height: u32,
+        sprite: usize,
+    },
+    // power decides which tiles can be mined, speed is a number of swings per SWING_TIME
+    Pickaxe {
+        power: u32,
+        speed: f32,
+    },
+    // swung in an arc towards the cursor, speed is a number of swings per second,
+    // crit_chance is the probability of dealing CRIT_MULTIPLIER times the damage
+    Sword {
+        damage: f32,
+        knockback: f32,
+        speed: f32,
+        crit_chance: f32,
+    },
+    // shoots the ammo item towards the cursor, one of it is used up for every shot
+    Bow {
+        damage: f32,
+        speed: f32,
+        launch_speed: f32,
+        ammo: ItemId,
+    },
+    // the item itself flies towards the cursor
+    Thrown {
+        damage: f32,
+        speed: f32,
+        launch_speed: f32,
+        #[serde(default)]
+        effect: ProjectileEffect,
+    },
+}
+
+// what a projectile does to the tiles it lands on, the radius is in tiles
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+pub enum ProjectileEffect {
+    // sticks in the tile
+    #[default]
+    None,
+    // breaks the tiles which a pickaxe of the given power could mine
+    Explode {
+        radius: u32,
+        power: u32,
+    },
+    // fills the empty cells with the tile of the given name
+    Place {
+        tile: String,
+        radius: u32,
+    },
+}
+
+#[derive(Deserialize)]
+pub struct ItemDef {
+    pub id: ItemId,
+    pub name: String,
+    pub icon: usize,
+    // the most items of this kind fitting into one inventory slot
+    pub max_stack: u32,
+    #[serde(default)]
+    pub kind: ItemKind,
+}
+
+#[derive(Resource)]
+pub struct ItemRegistry {
+    items: HashMap<ItemId, ItemDef>,
+}
+
+#[derive(Debug)]
+pub enum ItemError {
+    Parse(ron::error::SpannedError),
+    // the item parsed, but one of its fields would break the game
+    Invalid { item: ItemId, reason: &'static str },
+}
+
+// texture atlas made of items_strip.png
+#[derive(Resource)]
+pub struct ItemSprites(pub Handle<TextureAtlas>);
+
+impl ItemId {
+    pub fn new(id: &str) -> Self {
+        Self(String::from(id))
+    }
+}
+
+impl fmt::Display for ItemError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ItemError::Parse(err) => write!(f, "{err}"),
+            ItemError::Invalid { item, reason } => write!(f, "the item {} {reason}", item.0),
+        }
+    }
+}
+
+impl From<ron::error::SpannedError> for ItemError {
+    fn from(err: ron::error::SpannedError) -> Self {
+        ItemError::Parse(err)
+    }
+}
+
+fn positive(value: f32) -> bool {
+    value > 0.
+}
+
+fn not_negative(value: f32) -> bool {
+    value >= 0.
+}
+
+impl ItemKind {
+    // speeds are divided by, so none of them may be 0
+    fn check(&self) -> Result<(), &'static str> {
+        match self {
+            ItemKind::Material | ItemKind::Block { .. } => Ok(()),
+            ItemKind::Station { width, height, .. } => {
+                if *width == 0 || *height == 0 {
+                    return Err("is a station without a size");
+                }
+                Ok(())
+            }
+            ItemKind::Pickaxe { speed, .. } => {
+                if !positive(*speed) {
+                    return Err("has to swing faster than 0");
+                }
+                Ok(())
+            }
+            ItemKind::Sword {
+                damage,
+                knockback,
+                speed,
+                crit_chance,
+            } => {
+                if !positive(*speed) {
+                    return Err("has to swing faster than 0");
+                }
+                if !not_negative(*damage) || !not_negative(*knockback) {
+                    return Err("has a negative damage or knockback");
+                }
+                if !(0. ..=1.).contains(crit_chance) {
+                    return Err("has a crit chance outside of 0 to 1");
+                }
+                Ok(())
+            }
+            ItemKind::Bow {
+                damage,
+                speed,
+                launch_speed,
+                ..
+            }
+            | ItemKind::Thrown {
+                damage,
+                speed,
+                launch_speed,
+                ..
+            } => {
+                if !positive(*speed) || !positive(*launch_speed) {
+                    return Err("has to shoot faster than 0");
+                }
+                if !not_negative(*damage) {
+                    return Err("has a negative damage");
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl ItemRegistry {
+    // the whole file is rejected if any of the items in it is broken
+    pub fn from_ron(data: &str) -> Result<Self, ItemError> {
+        let defs: Vec<ItemDef> = ron::from_str(data)?;
+        let registry = Self {
+            items: defs.into_iter().map(|def| (def.id.clone(), def)).collect(),
+        };
+        for def in registry.items.values() {
+            let invalid = |reason| ItemError::Invalid {
+                item: def.id.clone(),
+                reason,
+            };
+            if def.max_stack == 0 {
+                return Err(invalid("does not fit into a slot"));
+            }
+            def.kind.check().map_err(invalid)?;
+            if let ItemKind::Bow { ammo, .. } = &def.kind {
+                if registry.get(ammo).is_none() {
+                    return Err(invalid("shoots an unknown item"));
+                }
+            }
+        }
+        Ok(registry)
+    }
+
+    pub fn get(&self, id: &ItemId) -> Option<&ItemDef> {
+        self.items.get(id)
+    }
+
+    pub fn max_stack(&self, id: &ItemId) -> u32 {
+        self.get(id).map_or(1, |def| def.max_stack)
+    }
+
+    pub fn icon_count(&self) -> usize {
+        self.items
+            .values()
+            .map(|def| def.icon + 1)
+            .max()
+            .unwrap_or(0)
+    }
+}
+
+impl Default for ItemRegistry {
+    fn default() -> Self {
+        Self::from_ron(include_str!("../assets/data/items.ron")).expect("invalid items.ron")
+    }
+}
+
+// a few small items the tests of the inventory and of the crafting share
+#[cfg(test)]
+pub fn test_registry() -> ItemRegistry {
+    ItemRegistry::from_ron(
+        r#"[
+            (id: "dirt", name: "Dirt", icon: 0, max_stack: 99),
+            (id: "stone", name: "Stone", icon: 1, max_stack: 99),
+            (id: "workbench", name: "Workbench", icon: 2, max_stack: 1),
+        ]"#,
+    )
+    .unwrap()
+}
+
+pub fn load_item_sprites(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    registry: Res<ItemRegistry>,
+    mut texture_atlases: ResMut<Assets<TextureAtlas>>,
+) {
+    let texture_handle: Handle<Image> = asset_server.load("items_strip.png");
+    let atlas = TextureAtlas::from_grid(
+        texture_handle,
+        Vec2::splat(ITEM_ICON_SIZE),
+        registry.icon_count(),
+        1,
+        None,
+        None,
+    );
+    commands.insert_resource(ItemSprites(texture_atlases.add(atlas)));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rejected(kind: &str) -> bool {
+        let data = format!(
+            r#"[
+                (id: "arrow", name: "Arrow", icon: 0, max_stack: 999),
+                (id: "weapon", name: "Weapon", icon: 1, max_stack: 1, kind: {kind}),
+            ]"#
+        );
+        matches!(
+            ItemRegistry::from_ron(&data),
+            Err(ItemError::Invalid { item, .. }) if item == ItemId::new("weapon")
+        )
+    }
+
+    #[test]
+    fn bundled_items_are_valid() {
+        assert!(ItemRegistry::from_ron(include_str!("../assets/data/items.ron")).is_ok());
+    }
+
+    #[test]
+    fn broken_weapons_are_rejected() {
+        assert!(!rejected(
+            "Sword(damage: 12.0, knockback: 120.0, speed: 2.5, crit_chance: 0.04)"
+        ));
+        assert!(rejected(
+            "Sword(damage: 12.0, knockback: 120.0, speed: 0.0, crit_chance: 0.04)"
+        ));
+        assert!(rejected(
+            "Sword(damage: 12.0, knockback: 120.0, speed: 2.5, crit_chance: 1.5)"
+        ));
+        assert!(rejected(
+            r#"Bow(damage: 10.0, speed: -1.0, launch_speed: 400.0, ammo: "arrow")"#
+        ));
+        assert!(rejected(
+            r#"Bow(damage: 10.0, speed: 2.0, launch_speed: 400.0, ammo: "bullet")"#
+        ));
+        assert!(rejected(
+            "Thrown(damage: 20.0, speed: 1.0, launch_speed: 0.0)"
+        ));
+        assert!(rejected("Pickaxe(power: 35, speed: 0.0)"));
+    }
+}
@@ -6,8 +6,11 @@ use bevy_rapier2d::prelude::*;
 use bevy_inspector_egui::WorldInspectorPlugin;
 
 mod map;
-use map::{handle_cover, spawn_cover_map};
-use map::{spawn_background, spawn_colliders, spawn_foreground_map, spawn_wall_map};
+use map::{handle_cover, spawn_cover_map, spawn_damage_map, TileChanged, TileDestroyed};
+use map::{spawn_background, spawn_foreground_map, spawn_wall_map};
+
+mod colliders;
+use colliders::{rebuild_changed_colliders, spawn_colliders};
 
 mod constants;
 use constants::world::*;
@@ -22,9 +25,97 @@ mod cursor;
 use cursor::{update_cursor_pos, CursorPos};
 
 mod tile;
+use tile::TileCollection;
+
+mod tile_damage;
+use tile_damage::{heal_damaged_tiles, update_damage_overlay};
+
+mod item;
+use item::{load_item_sprites, ItemRegistry};
+
+mod inventory;
+
+mod death;
+use death::spawn_death_screen;
+use death::{count_down_respawn, delete_dead_character, kill_player, set_spawn_at_bed};
+
+mod enemy;
+use enemy::{kill_enemies, slime_ai, slime_contact_damage};
+
+mod spawning;
+use spawning::{spawn_enemies, EnemySpawner};
+
+mod clock;
+use clock::{advance_world_clock, reset_world_clock, WorldClock};
+
+mod health;
+use health::{apply_damage, apply_fall_damage, regenerate_health, spawn_health_bar};
+use health::{float_damage_numbers, spawn_damage_numbers, track_fall_speed};
+use health::{update_health_bar, DamageEvent};
+
+mod melee;
+use melee::{start_swing, swing_hits, update_swings};
+
+mod projectiles;
+use projectiles::{expire_projectiles, fire_projectiles, orient_projectiles, projectile_hits};
+
+mod hotbar;
+use hotbar::{position_hud, select_hotbar_slot, spawn_hotbar, update_hotbar};
+
+mod drops;
+use drops::{attract_item_drops, despawn_expired_drops, merge_item_drops};
+use drops::{pick_up_item_drops, spawn_station_drops, spawn_tile_drops, ItemPickedUp};
 
 mod destroy_tiles;
-use destroy_tiles::destroy_tile_after_click;
+use destroy_tiles::{destroy_tile_after_click, spawn_mining_progress};
+
+mod crafting;
+use crafting::{craft_clicked_recipe, spawn_crafting_panel, RecipeRegistry};
+use crafting::{toggle_crafting_panel, update_crafting_panel};
+
+mod stations;
+use stations::{
+    find_nearby_stations, load_station_sprites, NearbyStations, StationDestroyed, StationMap,
+};
+
+mod place_tiles;
+use place_tiles::place_tile_after_click;
+
+mod migrations;
+mod save;
+use save::{autosave_game, load_saved_game, save_and_quit_to_menu, save_game_on_exit};
+use save::{save_game_on_key, spawn_saved_drops, spawn_saved_stations, Autosave};
+
+mod save_chunks;
+use save_chunks::{mark_dirty_chunks, ChunkCache};
+
+mod game_state;
+use game_state::AppState;
+use game_state::{despawn_world, finish_loading, pause_physics, resume_physics, toggle_pause};
+
+mod menu;
+use menu::{despawn_menu, despawn_menu_camera, handle_menu_buttons, spawn_main_menu};
+use menu::{spawn_menu_camera, spawn_pause_overlay};
+
+mod actions;
+use actions::load_input_settings;
+
+mod rebinding;
+use rebinding::{capture_binding, handle_rebinding_buttons, reset_rebinding};
+use rebinding::{update_rebinding_screen, Rebinding};
+
+mod gamepad;
+use gamepad::{move_gamepad_cursor, navigate_menus_with_gamepad, point_ui_with_gamepad_cursor};
+use gamepad::{spawn_gamepad_cursor, update_gamepad_cursor_sprite, GamepadCursor, GamepadFocus};
+
+mod world_settings;
+
+mod save_slots;
+use save_slots::{tick_play_clock, PlayClock};
+
+mod save_select;
+use save_select::{handle_select_buttons, refresh_save_selection, type_in_select_form};
+use save_select::{update_save_select, SaveSelection};
 
 fn main() {
     let mut app = App::new();
@@ -42,25 +133,215 @@ fn main() {
             .set(ImagePlugin::default_nearest()),
     )
     .init_resource::<CursorPos>()
+    .init_resource::<TileCollection>()
+    .init_resource::<ItemRegistry>()
+    .init_resource::<RecipeRegistry>()
+    .init_resource::<NearbyStations>()
+    .init_resource::<StationMap>()
+    .init_resource::<Autosave>()
+    .init_resource::<ChunkCache>()
+    .init_resource::<PlayClock>()
+    .init_resource::<WorldClock>()
+    .init_resource::<EnemySpawner>()
+    .init_resource::<SaveSelection>()
+    .init_resource::<Rebinding>()
+    .init_resource::<GamepadCursor>()
+    .init_resource::<GamepadFocus>()
+    .add_event::<TileChanged>()
+    .add_event::<TileDestroyed>()
+    .add_event::<StationDestroyed>()
+    .add_event::<ItemPickedUp>()
+    .add_event::<DamageEvent>()
     .add_plugin(TilemapPlugin)
     .add_plugin(RapierPhysicsPlugin::<NoUserData>::pixels_per_meter(
         PHYSICS_SCALE,
     ))
-    .add_startup_system(spawn_background)
-    .add_startup_system(spawn_wall_map)
-    .add_startup_system(spawn_foreground_map)
-    .add_startup_system(spawn_cover_map)
-    .add_startup_system_to_stage(StartupStage::PostStartup, spawn_colliders)
-    .add_startup_system(spawn_player)
-    .add_system(camera_movement)
-    // player systems
-    .add_system(player_jump)
-    .add_system(player_jump_reset)
-    .add_system(player_movement)
-    .add_system(handle_cover)
-    .add_system(update_cursor_pos)
-    .add_system(destroy_tile_after_click)
-    .add_system(bevy::window::close_on_esc);
+    .add_state(AppState::MainMenu)
+    .add_startup_system(load_item_sprites)
+    .add_startup_system(load_station_sprites)
+    .add_startup_system(load_input_settings)
+    // menus
+    .add_system_set(
+        SystemSet::on_enter(AppState::MainMenu)
+            .with_system(despawn_world)
+            .with_system(spawn_menu_camera)
+            .with_system(spawn_main_menu),
+    )
+    .add_system_set(SystemSet::on_exit(AppState::MainMenu).with_system(despawn_menu))
+    .add_system_set(SystemSet::on_pause(AppState::MainMenu).with_system(despawn_menu))
+    .add_system_set(SystemSet::on_resume(AppState::MainMenu).with_system(spawn_main_menu))
+    .add_system_set(SystemSet::on_enter(AppState::WorldSelect).with_system(refresh_save_selection))
+    .add_system_set(
+        SystemSet::on_update(AppState::WorldSelect)
+            .with_system(handle_select_buttons)
+            .with_system(type_in_select_form)
+            .with_system(
+                update_save_select
+                    .after(handle_select_buttons)
+                    .after(type_in_select_form),
+            ),
+    )
+    .add_system_set(SystemSet::on_exit(AppState::WorldSelect).with_system(despawn_menu))
+    .add_system(handle_menu_buttons)
+    .add_system(
+        navigate_menus_with_gamepad
+            .before(handle_menu_buttons)
+            .before(handle_select_buttons)
+            .before(handle_rebinding_buttons),
+    )
+    // loading
+    .add_system_set(
+        SystemSet::on_enter(AppState::Loading)
+            .with_system(despawn_menu_camera)
+            .with_system(load_saved_game),
+    )
+    .add_system_set(SystemSet::on_update(AppState::Loading).with_system(finish_loading))
+    .add_system_set(
+        SystemSet::on_enter(AppState::InGame)
+            .with_system(spawn_background)
+            .with_system(spawn_wall_map)
+            .with_system(spawn_foreground_map)
+            .with_system(spawn_damage_map)
+            .with_system(spawn_cover_map)
+            .with_system(spawn_player)
+            .with_system(spawn_gamepad_cursor)
+            .with_system(reset_world_clock)
+            .with_system(spawn_mining_progress)
+            .with_system(spawn_crafting_panel),
+    )
+    // pause
+    .add_system(toggle_pause.after(capture_binding))
+    .add_system_set(
+        SystemSet::on_enter(AppState::Paused)
+            .with_system(pause_physics)
+            .with_system(spawn_pause_overlay),
+    )
+    .add_system_set(
+        SystemSet::on_exit(AppState::Paused)
+            .with_system(resume_physics)
+            .with_system(despawn_menu),
+    )
+    // the overlay makes room for the settings pushed on top of it
+    .add_system_set(SystemSet::on_pause(AppState::Paused).with_system(despawn_menu))
+    .add_system_set(SystemSet::on_resume(AppState::Paused).with_system(spawn_pause_overlay))
+    .add_system_set(SystemSet::on_update(AppState::Paused).with_system(save_and_quit_to_menu))
+    // settings
+    .add_system_set(SystemSet::on_enter(AppState::Settings).with_system(reset_rebinding))
+    .add_system_set(
+        SystemSet::on_update(AppState::Settings)
+            .with_system(capture_binding)
+            .with_system(handle_rebinding_buttons)
+            .with_system(
+                update_rebinding_screen
+                    .after(capture_binding)
+                    .after(handle_rebinding_buttons),
+            ),
+    )
+    .add_system_set(SystemSet::on_exit(AppState::Settings).with_system(despawn_menu))
+    // death
+    .add_system_set(
+        SystemSet::on_enter(AppState::Dead)
+            .with_system(pause_physics)
+            .with_system(spawn_death_screen)
+            .with_system(delete_dead_character),
+    )
+    .add_system_set(SystemSet::on_update(AppState::Dead).with_system(count_down_respawn))
+    .add_system_set(
+        SystemSet::on_exit(AppState::Dead)
+            .with_system(resume_physics)
+            .with_system(despawn_menu),
+    )
+    .add_system_set(
+        SystemSet::on_update(AppState::InGame)
+            // parts of the world which need the spawned maps and player
+            .with_system(spawn_saved_stations)
+            .with_system(spawn_saved_drops)
+            .with_system(spawn_colliders)
+            .with_system(spawn_hotbar)
+            .with_system(camera_movement)
+            // player systems
+            .with_system(player_jump)
+            .with_system(player_jump_reset)
+            .with_system(player_movement)
+            .with_system(handle_cover)
+            // health
+            .with_system(apply_fall_damage)
+            .with_system(track_fall_speed.after(apply_fall_damage))
+            .with_system(apply_damage.after(apply_fall_damage))
+            .with_system(regenerate_health.after(apply_damage))
+            .with_system(kill_player.after(apply_damage))
+            .with_system(set_spawn_at_bed)
+            // enemies
+            .with_system(advance_world_clock)
+            .with_system(spawn_enemies.after(advance_world_clock))
+            .with_system(slime_ai)
+            .with_system(slime_contact_damage.before(apply_damage))
+            .with_system(kill_enemies.after(apply_damage))
+            // weapons
+            .with_system(start_swing.after(update_cursor_pos))
+            .with_system(update_swings.after(start_swing))
+            .with_system(swing_hits.after(update_swings).before(apply_damage))
+            .with_system(fire_projectiles.after(update_cursor_pos))
+            .with_system(orient_projectiles)
+            .with_system(projectile_hits.before(apply_damage))
+            .with_system(expire_projectiles.after(projectile_hits))
+            .with_system(spawn_damage_numbers)
+            .with_system(float_damage_numbers)
+            .with_system(move_gamepad_cursor.before(update_cursor_pos))
+            .with_system(update_cursor_pos)
+            .with_system(update_gamepad_cursor_sprite.after(update_cursor_pos))
+            .with_system(
+                point_ui_with_gamepad_cursor
+                    .after(update_cursor_pos)
+                    .before(craft_clicked_recipe),
+            )
+            .with_system(destroy_tile_after_click)
+            .with_system(place_tile_after_click)
+            .with_system(
+                rebuild_changed_colliders
+                    .after(destroy_tile_after_click)
+                    .after(place_tile_after_click)
+                    .after(projectile_hits),
+            )
+            .with_system(heal_damaged_tiles)
+            .with_system(update_damage_overlay.after(destroy_tile_after_click))
+            // item drops
+            .with_system(
+                spawn_tile_drops
+                    .after(destroy_tile_after_click)
+                    .after(projectile_hits),
+            )
+            .with_system(spawn_station_drops.after(destroy_tile_after_click))
+            .with_system(merge_item_drops)
+            .with_system(attract_item_drops)
+            .with_system(pick_up_item_drops)
+            .with_system(despawn_expired_drops)
+            // hud
+            .with_system(select_hotbar_slot)
+            .with_system(update_hotbar.after(select_hotbar_slot))
+            .with_system(position_hud)
+            .with_system(spawn_health_bar)
+            .with_system(update_health_bar.after(regenerate_health))
+            // crafting
+            .with_system(toggle_crafting_panel)
+            .with_system(craft_clicked_recipe)
+            .with_system(find_nearby_stations)
+            .with_system(
+                update_crafting_panel
+                    .after(craft_clicked_recipe)
+                    .after(find_nearby_stations),
+            )
+            // saving
+            .with_system(mark_dirty_chunks)
+            .with_system(save_game_on_key.after(mark_dirty_chunks))
+            .with_system(autosave_game.after(mark_dirty_chunks))
+            .with_system(tick_play_clock),
+    )
+    .add_system(
+        save_game_on_exit
+            .after(mark_dirty_chunks)
+            .after(handle_menu_buttons),
+    );
 
     #[cfg(feature = "debug")]
     app.add_plugin(WorldInspectorPlugin::new())
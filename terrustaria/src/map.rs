@@ -1,36 +1,157 @@
+use std::ops::Range;
+
 use bevy::prelude::*;
 use bevy_ecs_tilemap::prelude::*;
-use bevy_rapier2d::prelude::*;
-use rand::prelude::*;
+use rand::{rngs::StdRng, Rng};
 
-use crate::constants::{
-    collision_groups::MAP_COLLIDE_WITH_ALL_EXCEPT_MAP, depth::*, map::*, player::VISION_RADIUS,
-};
+use crate::constants::{depth::*, map::*, player::VISION_RADIUS};
+use crate::game_state::WorldEntity;
 use crate::player::Player;
+use crate::save::WorldSave;
 use crate::tile::*;
+use crate::world_settings::WorldSettings;
 
 #[derive(Component)]
 pub struct WithColliders;
 #[derive(Component)]
+pub struct WallMap;
+#[derive(Component)]
 pub struct CoverTile;
 #[derive(Component)]
 pub struct CoverMap;
+#[derive(Component)]
+pub struct DamageMap;
+
+// sent whenever a tile of the foreground map is mined or placed
+pub struct TileChanged(pub TilePos);
+
+// sent when a foreground tile gets broken
+pub struct TileDestroyed {
+    pub tile_pos: TilePos,
+    pub texture_index: TileTextureIndex,
+}
+
+// breaks the foreground tile in the cell, the drop follows from the TileDestroyed event
+pub fn remove_foreground_tile(
+    commands: &mut Commands,
+    tile_storage: &mut TileStorage,
+    tile_pos: TilePos,
+    texture_index: TileTextureIndex,
+    tile_changes: &mut EventWriter<TileChanged>,
+    destroyed_tiles: &mut EventWriter<TileDestroyed>,
+) {
+    let Some(tile_entity) = tile_storage.get(&tile_pos) else {
+        return;
+    };
+    commands.entity(tile_entity).despawn_recursive();
+    tile_storage.remove(&tile_pos);
+    tile_changes.send(TileChanged(tile_pos));
+    destroyed_tiles.send(TileDestroyed {
+        tile_pos,
+        texture_index,
+    });
+}
+
+// content of a cell of the foreground map
+#[derive(Clone, Copy, Debug)]
+pub enum GridCell {
+    Empty,
+    Tile(TileTextureIndex),
+}
+
+// the foreground of a world without any entities, it can be generated, or copied
+// from the tilemap to look at the world outside of the ecs
+#[derive(Clone, Debug)]
+pub struct WorldGrid {
+    size: TilemapSize,
+    // column by column, starting from the left one
+    cells: Vec<GridCell>,
+}
+
+impl WorldGrid {
+    pub fn empty(size: TilemapSize) -> Self {
+        Self {
+            size,
+            cells: vec![GridCell::Empty; (size.x * size.y) as usize],
+        }
+    }
+
+    // only the given columns are read, from the bottom row up to the top of the map,
+    // every other cell of the grid stays empty
+    pub fn from_storage(
+        tile_storage: &TileStorage,
+        tile_q: &Query<&TileTextureIndex>,
+        columns: Range<u32>,
+        bottom: u32,
+    ) -> Self {
+        let mut grid = Self::empty(tile_storage.size);
+        for x in columns.start..columns.end.min(grid.size.x) {
+            for y in bottom..grid.size.y {
+                let tile_pos = TilePos { x, y };
+                let Some(texture_index) = tile_storage
+                    .get(&tile_pos)
+                    .and_then(|entity| tile_q.get(entity).ok())
+                else {
+                    continue;
+                };
+                grid.set(&tile_pos, GridCell::Tile(*texture_index));
+            }
+        }
+        grid
+    }
 
-fn random_in_range(range: f32) -> f32 {
-    let val: f32 = thread_rng().gen();
+    pub fn size(&self) -> TilemapSize {
+        self.size
+    }
+
+    // everything outside of the map is empty
+    pub fn get(&self, tile_pos: &TilePos) -> GridCell {
+        if tile_pos.x >= self.size.x || tile_pos.y >= self.size.y {
+            return GridCell::Empty;
+        }
+        self.cells[(tile_pos.x * self.size.y + tile_pos.y) as usize]
+    }
+
+    pub fn set(&mut self, tile_pos: &TilePos, cell: GridCell) {
+        if tile_pos.x < self.size.x && tile_pos.y < self.size.y {
+            self.cells[(tile_pos.x * self.size.y + tile_pos.y) as usize] = cell;
+        }
+    }
+
+    pub fn is_solid(&self, tile_pos: &TilePos) -> bool {
+        !matches!(self.get(tile_pos), GridCell::Empty)
+    }
+
+    // row of the first cell with nothing solid above it, the top of the map without one
+    pub fn sky_row(&self, x: u32) -> u32 {
+        (0..self.size.y)
+            .rev()
+            .find(|y| self.is_solid(&TilePos { x, y: *y }))
+            .map_or(0, |y| y + 1)
+    }
+}
+
+// all the randomness of the generation comes from the seeded rng of the world
+fn random_in_range(rng: &mut StdRng, range: f32) -> f32 {
+    let val: f32 = rng.gen();
     val * range
 }
 
-fn random_u32(a: u32, b: u32) -> u32 {
-    let val: f32 = thread_rng().gen();
+fn random_u32(rng: &mut StdRng, a: u32, b: u32) -> u32 {
+    let val: f32 = rng.gen();
     (((b - a) as f32) * val - 0.001) as u32 + a
 }
 
-fn get_random_tile_type(tile_types: &TileCollection, pos: &TilePos) -> usize {
-    let rarity_sum = tile_types.rarity_sum_valid(pos);
-    let mut random = random_in_range(rarity_sum);
+fn get_random_tile_type(
+    rng: &mut StdRng,
+    tile_types: &TileCollection,
+    pos: &TilePos,
+    map_size: &TilemapSize,
+) -> usize {
+    let rarity_sum = tile_types.rarity_sum_valid(pos, map_size);
+    let mut random = random_in_range(rng, rarity_sum);
     for (i, tile_type) in tile_types.get_tiles().iter().enumerate() {
-        if tile_type.is_valid(pos) {
+        if tile_type.is_valid(pos, map_size) {
             if random < tile_type.get_rarity() {
                 return i;
             } else {
@@ -42,7 +163,9 @@ fn get_random_tile_type(tile_types: &TileCollection, pos: &TilePos) -> usize {
 }
 
 fn create_cave(
+    rng: &mut StdRng,
     tile_types: &TileCollection,
+    map_size: &TilemapSize,
     visited: &mut [Vec<bool>],
     start_pos: TilePos,
     mut size: u32,
@@ -66,9 +189,11 @@ fn create_cave(
                 x: (pos.x as i32 + dx[i]) as u32,
                 y: (pos.y as i32 + dy[i]) as u32,
             };
-            if stone_tile.is_valid(&new_pos) && !visited[new_pos.x as usize][new_pos.y as usize] {
+            if stone_tile.is_valid(&new_pos, map_size)
+                && !visited[new_pos.x as usize][new_pos.y as usize]
+            {
                 // some randomization
-                if random_in_range(start_size) <= start_size - (processed as f32) {
+                if random_in_range(rng, start_size) <= start_size - (processed as f32) {
                     visited[new_pos.x as usize][new_pos.y as usize] = true;
                     in_cave.push(new_pos);
                 }
@@ -88,8 +213,8 @@ fn fill_tilemap_with_set_structure_id(
     tile_storage: &mut TileStorage,
     map_name: &str,
 ) {
-    for x in 0..MAP_SIZE.x {
-        for y in 0..MAP_SIZE.y - BUILDING_HEIGHT {
+    for x in 0..tile_storage.size.x {
+        for y in 0..tile_storage.size.y - BUILDING_HEIGHT {
             let tile_pos = TilePos { x, y };
             let tile_entity = commands
                 .spawn(TileBundle {
@@ -105,21 +230,19 @@ fn fill_tilemap_with_set_structure_id(
     }
 }
 
-// fills randomly tilemap with colliders and textures, does not fill building area
-fn fill_tilemap_randomly(
-    tilemap_id: TilemapId,
-    commands: &mut Commands,
-    tile_storage: &mut TileStorage,
-    map_name: &str,
-) {
+// generates the foreground tiles of a new world, the building area stays empty
+pub fn generate_world_grid(rng: &mut StdRng, map_size: TilemapSize) -> WorldGrid {
     let tile_types = TileCollection::new();
-    let mut visited = vec![vec![false; MAP_SIZE.y as usize]; MAP_SIZE.x as usize];
+    let mut grid = WorldGrid::empty(map_size);
+    let mut visited = vec![vec![false; map_size.y as usize]; map_size.x as usize];
 
     // create a cave
-    let start_x = random_u32(0, MAP_SIZE.x);
-    let start_y = random_u32(0, 10);
+    let start_x = random_u32(rng, 0, map_size.x);
+    let start_y = random_u32(rng, 0, 10);
     let in_cave = create_cave(
+        rng,
         &tile_types,
+        &map_size,
         &mut visited,
         TilePos {
             x: start_x,
@@ -128,23 +251,15 @@ fn fill_tilemap_randomly(
         MAX_CAVE_SIZE,
     );
     for tile_pos in in_cave {
-        let x = tile_pos.x;
-        let y = tile_pos.y;
-        let tile_entity = commands
-            .spawn(TileBundle {
-                position: tile_pos,
-                tilemap_id,
-                texture_index: tile_types.stone_tile().get_texture_index(),
-                ..Default::default()
-            })
-            .insert(Name::new(format!("{map_name}Tile({x},{y})")))
-            .id();
-        tile_storage.set(&tile_pos, tile_entity);
+        grid.set(
+            &tile_pos,
+            GridCell::Tile(tile_types.stone_tile().get_texture_index()),
+        );
     }
 
     // fill standard tiles
-    for x in 0..MAP_SIZE.x {
-        for y in 0..MAP_SIZE.y - BUILDING_HEIGHT {
+    for x in 0..map_size.x {
+        for y in 0..map_size.y - BUILDING_HEIGHT {
             if visited[x as usize][y as usize] {
                 continue;
             } else {
@@ -154,17 +269,39 @@ fn fill_tilemap_randomly(
             let tile_pos = TilePos { x, y };
             let mut idx: usize;
             loop {
-                idx = get_random_tile_type(&tile_types, &tile_pos);
-                if tile_types.at(idx).is_valid(&tile_pos) {
+                idx = get_random_tile_type(rng, &tile_types, &tile_pos, &map_size);
+                if tile_types.at(idx).is_valid(&tile_pos, &map_size) {
                     break;
                 }
             }
+            grid.set(
+                &tile_pos,
+                GridCell::Tile(tile_types.at(idx).get_texture_index()),
+            );
+        }
+    }
+    grid
+}
 
+// rebuilds the tilemap from a save, empty cells and unknown tiles stay empty
+fn fill_tilemap_from_save(
+    saved_texture: impl Fn(&TilePos) -> Option<TileTextureIndex>,
+    tilemap_id: TilemapId,
+    commands: &mut Commands,
+    tile_storage: &mut TileStorage,
+    map_name: &str,
+) {
+    for x in 0..tile_storage.size.x {
+        for y in 0..tile_storage.size.y {
+            let tile_pos = TilePos { x, y };
+            let Some(texture_index) = saved_texture(&tile_pos) else {
+                continue;
+            };
             let tile_entity = commands
                 .spawn(TileBundle {
                     position: tile_pos,
                     tilemap_id,
-                    texture_index: tile_types.at(idx).get_texture_index(),
+                    texture_index,
                     ..Default::default()
                 })
                 .insert(Name::new(format!("{map_name}Tile({x},{y})")))
@@ -174,19 +311,27 @@ fn fill_tilemap_randomly(
     }
 }
 
+// without a save only the surroundings of the spawn point are uncovered
 fn fill_cover_map(
     texture_index: TileTextureIndex,
     tilemap_id: TilemapId,
     commands: &mut Commands,
     tile_storage: &mut TileStorage,
     map_name: &str,
+    saved: Option<&WorldSave>,
 ) {
-    for x in 0..MAP_SIZE.x {
-        for y in 0..MAP_SIZE.y - BUILDING_HEIGHT {
-            let covered = y < MAP_SIZE.y - BUILDING_HEIGHT - VISION_RADIUS
-                || x <= MAP_SIZE.x / 2 - VISION_RADIUS
-                || x >= MAP_SIZE.x / 2 + VISION_RADIUS;
+    let map_size = tile_storage.size;
+    for x in 0..map_size.x {
+        for y in 0..map_size.y - BUILDING_HEIGHT {
             let tile_pos = TilePos { x, y };
+            let covered = match saved {
+                Some(saved) => !saved.is_explored(&tile_pos),
+                None => {
+                    y < map_size.y - BUILDING_HEIGHT - VISION_RADIUS
+                        || x <= map_size.x / 2 - VISION_RADIUS
+                        || x >= map_size.x / 2 + VISION_RADIUS
+                }
+            };
             let tile_entity = commands
                 .spawn(TileBundle {
                     position: tile_pos,
@@ -204,63 +349,73 @@ fn fill_cover_map(
     }
 }
 
-pub fn spawn_colliders(
-    mut commands: Commands,
-    tilemap_q: Query<&TileStorage, With<WithColliders>>,
-    tile_q: Query<&mut TilePos>,
-) {
-    for tilemap_storage in tilemap_q.iter() {
-        for tile_entity in tilemap_storage.iter().flatten() {
-            let tile_pos = tile_q.get(*tile_entity).unwrap();
-            let transform_bundle = TransformBundle::from(Transform::from_translation(
-                (Vec2::new(
-                    tile_pos.x as f32 * GRID_SIZE.x,
-                    tile_pos.y as f32 * GRID_SIZE.y,
-                ) + map_transform_vec2())
-                .extend(0.),
-            ));
-
-            commands
-                .entity(*tile_entity)
-                .insert(RigidBody::Fixed)
-                .insert(Collider::cuboid(COLLIDER_SIZE.x, COLLIDER_SIZE.y))
-                .insert(MAP_COLLIDE_WITH_ALL_EXCEPT_MAP)
-                .insert(ActiveEvents::COLLISION_EVENTS)
-                .insert(transform_bundle);
-        }
-    }
-}
-
 fn spawn_map(
     mut commands: Commands,
     asset_server: Res<AssetServer>,
     z_translation: f32,
     map_name: &str,
+    settings: &WorldSettings,
+    saved: Option<&WorldSave>,
 ) {
     let mut texture_handle: Handle<Image> = asset_server.load("tiles_strip.png");
-    let mut tile_storage = TileStorage::empty(MAP_SIZE);
+    let tile_types = TileCollection::new();
+    let map_size = settings.map_size();
+    let mut tile_storage = TileStorage::empty(map_size);
     let tilemap_entity = commands
         .spawn_empty()
         .insert(Name::new(format!("{map_name}Map")))
+        .insert(WorldEntity)
         .id();
 
     if map_name == "Wall" {
         texture_handle = asset_server.load("walls_strip.png");
-        fill_tilemap_with_set_structure_id(
-            TileTextureIndex(3),
-            TilemapId(tilemap_entity),
-            &mut commands,
-            &mut tile_storage,
-            map_name,
-        );
+        commands.entity(tilemap_entity).insert(WallMap);
+        if let Some(saved) = saved {
+            fill_tilemap_from_save(
+                |tile_pos| {
+                    let name = saved.wall(tile_pos)?;
+                    Some(tile_types.wall_by_name(name)?.get_texture_index())
+                },
+                TilemapId(tilemap_entity),
+                &mut commands,
+                &mut tile_storage,
+                map_name,
+            );
+        } else {
+            fill_tilemap_with_set_structure_id(
+                tile_types.dirt_wall().get_texture_index(),
+                TilemapId(tilemap_entity),
+                &mut commands,
+                &mut tile_storage,
+                map_name,
+            );
+        }
     } else if map_name == "Foreground" {
         commands.entity(tilemap_entity).insert(WithColliders);
-        fill_tilemap_randomly(
-            TilemapId(tilemap_entity),
-            &mut commands,
-            &mut tile_storage,
-            map_name,
-        );
+        if let Some(saved) = saved {
+            fill_tilemap_from_save(
+                |tile_pos| {
+                    let name = saved.tile(tile_pos)?;
+                    Some(tile_types.by_name(name)?.get_texture_index())
+                },
+                TilemapId(tilemap_entity),
+                &mut commands,
+                &mut tile_storage,
+                map_name,
+            );
+        } else {
+            let grid = generate_world_grid(&mut settings.rng(), map_size);
+            fill_tilemap_from_save(
+                |tile_pos| match grid.get(tile_pos) {
+                    GridCell::Tile(texture_index) => Some(texture_index),
+                    GridCell::Empty => None,
+                },
+                TilemapId(tilemap_entity),
+                &mut commands,
+                &mut tile_storage,
+                map_name,
+            );
+        }
     } else if map_name == "Cover" {
         texture_handle = asset_server.load("tiles_big.png");
         commands.entity(tilemap_entity).insert(CoverMap);
@@ -270,17 +425,21 @@ fn spawn_map(
             &mut commands,
             &mut tile_storage,
             map_name,
+            saved,
         );
+    } else if map_name == "Damage" {
+        // filled with cracked tiles only while the foreground tiles are being mined
+        commands.entity(tilemap_entity).insert(DamageMap);
     }
 
     commands.entity(tilemap_entity).insert(TilemapBundle {
         grid_size: GRID_SIZE,
         map_type: MAP_TYPE,
-        size: MAP_SIZE,
+        size: map_size,
         storage: tile_storage,
         texture: TilemapTexture::Single(texture_handle),
         tile_size: TILE_SIZE,
-        transform: Transform::from_translation(map_transform_vec2().extend(z_translation)),
+        transform: Transform::from_translation(settings.map_transform().extend(z_translation)),
         ..Default::default()
     });
 }
@@ -294,19 +453,64 @@ pub fn spawn_background(mut commands: Commands, asset_server: Res<AssetServer>)
             transform: Transform::from_xyz(0.0, 0.0, Z_BACKGROUND),
             ..Default::default()
         })
-        .insert(Name::new("Background"));
+        .insert(Name::new("Background"))
+        .insert(WorldEntity);
 }
 
-pub fn spawn_wall_map(commands: Commands, asset_server: Res<AssetServer>) {
-    spawn_map(commands, asset_server, Z_WALLS, "Wall");
+pub fn spawn_wall_map(
+    commands: Commands,
+    asset_server: Res<AssetServer>,
+    settings: Res<WorldSettings>,
+    saved: Option<Res<WorldSave>>,
+) {
+    spawn_map(
+        commands,
+        asset_server,
+        Z_WALLS,
+        "Wall",
+        &settings,
+        saved.as_deref(),
+    );
 }
 
-pub fn spawn_foreground_map(commands: Commands, asset_server: Res<AssetServer>) {
-    spawn_map(commands, asset_server, Z_FOREGROUND, "Foreground");
+pub fn spawn_foreground_map(
+    commands: Commands,
+    asset_server: Res<AssetServer>,
+    settings: Res<WorldSettings>,
+    saved: Option<Res<WorldSave>>,
+) {
+    spawn_map(
+        commands,
+        asset_server,
+        Z_FOREGROUND,
+        "Foreground",
+        &settings,
+        saved.as_deref(),
+    );
 }
 
-pub fn spawn_cover_map(commands: Commands, asset_server: Res<AssetServer>) {
-    spawn_map(commands, asset_server, Z_COVER, "Cover");
+pub fn spawn_damage_map(
+    commands: Commands,
+    asset_server: Res<AssetServer>,
+    settings: Res<WorldSettings>,
+) {
+    spawn_map(commands, asset_server, Z_DAMAGE, "Damage", &settings, None);
+}
+
+pub fn spawn_cover_map(
+    commands: Commands,
+    asset_server: Res<AssetServer>,
+    settings: Res<WorldSettings>,
+    saved: Option<Res<WorldSave>>,
+) {
+    spawn_map(
+        commands,
+        asset_server,
+        Z_COVER,
+        "Cover",
+        &settings,
+        saved.as_deref(),
+    );
 }
 
 pub fn handle_cover(
@@ -315,12 +519,12 @@ pub fn handle_cover(
     tilemap_q: Query<(&TilemapSize, &TilemapGridSize, &TilemapType, &TileStorage), With<CoverMap>>,
 ) {
     let player_transform = player_q.single().translation;
+    let (map_size, grid_size, map_type, tile_storage) = tilemap_q.single();
     let mut player_pos = Vec2::new(player_transform.x, player_transform.y);
     player_pos = Vec2::new(
-        player_pos.x - map_transform_vec2().x,
-        player_pos.y - map_transform_vec2().y,
+        player_pos.x - map_transform_vec2(map_size).x,
+        player_pos.y - map_transform_vec2(map_size).y,
     );
-    let (map_size, grid_size, map_type, tile_storage) = tilemap_q.single();
 
     let mut possible_tile_pos: Vec<Option<TilePos>> = Vec::new();
     let radius = VISION_RADIUS as f32;
@@ -351,8 +555,11 @@ pub fn handle_cover(
 
     for pos_pos in possible_tile_pos.into_iter().flatten() {
         if let Some(tile_entity) = tile_storage.get(&pos_pos) {
+            // only uncovering counts as a change, which makes the chunk dirty in the save
             if let Ok(mut tile_vis) = cover_q.get_mut(tile_entity) {
-                tile_vis.0 = false;
+                if tile_vis.0 {
+                    tile_vis.0 = false;
+                }
             }
         }
     }
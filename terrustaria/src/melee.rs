@@ -0,0 +1,175 @@
+use std::f32::consts::{FRAC_PI_4, SQRT_2};
+
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+use rand::prelude::*;
+
+use crate::actions::{Action, Actions};
+use crate::constants::{collision_groups::WEAPON_COLLIDE_WITH_ENEMIES, combat::*, depth::Z_SWING};
+use crate::cursor::{CursorPos, UiPointer};
+use crate::game_state::WorldEntity;
+use crate::health::{DamageEvent, DamageSource, Health};
+use crate::hotbar::Hotbar;
+use crate::inventory::Inventory;
+use crate::item::{ItemKind, ItemRegistry, ItemSprites};
+use crate::player::Player;
+
+// sensor sweeping around the player, it hits every enemy at most once
+#[derive(Component)]
+pub struct Swing {
+    timer: Timer,
+    start_angle: f32,
+    end_angle: f32,
+    damage: f32,
+    knockback: f32,
+    crit_chance: f32,
+    hit: Vec<Entity>,
+}
+
+impl Swing {
+    fn angle(&self) -> f32 {
+        self.start_angle + (self.end_angle - self.start_angle) * self.timer.percent()
+    }
+}
+
+// the middle of the blade points away from the player at the given angle
+fn swing_transform(player_pos: Vec2, angle: f32) -> Transform {
+    let direction = Vec2::new(angle.cos(), angle.sin());
+    Transform::from_translation((player_pos + direction * SWORD_REACH / 2.).extend(Z_SWING))
+        .with_rotation(Quat::from_rotation_z(angle))
+}
+
+// holding the use action keeps swinging
+#[allow(clippy::too_many_arguments)]
+pub fn start_swing(
+    mut commands: Commands,
+    actions: Actions,
+    ui_pointer: UiPointer,
+    cursor_pos: Res<CursorPos>,
+    registry: Res<ItemRegistry>,
+    item_sprites: Res<ItemSprites>,
+    player_q: Query<(&Transform, &Inventory, &Hotbar), With<Player>>,
+    swing_q: Query<(), With<Swing>>,
+) {
+    if !actions.pressed(Action::UseItem) || ui_pointer.over_ui() || !swing_q.is_empty() {
+        return;
+    }
+    let Ok((player_transform, inventory, hotbar)) = player_q.get_single() else {
+        return;
+    };
+    let Some(item_def) = hotbar.selected_item(inventory, &registry) else {
+        return;
+    };
+    let ItemKind::Sword {
+        damage,
+        knockback,
+        speed,
+        crit_chance,
+    } = item_def.kind
+    else {
+        return;
+    };
+
+    // swords always swing downwards, whichever side the cursor is on
+    let player_pos = player_transform.translation.truncate();
+    let aim = cursor_pos.0.truncate() - player_pos;
+    let aim_angle = aim.y.atan2(aim.x);
+    let (start_angle, end_angle) = if aim.x >= 0. {
+        (aim_angle + SWING_ARC / 2., aim_angle - SWING_ARC / 2.)
+    } else {
+        (aim_angle - SWING_ARC / 2., aim_angle + SWING_ARC / 2.)
+    };
+
+    commands
+        .spawn(SpatialBundle::from_transform(swing_transform(
+            player_pos,
+            start_angle,
+        )))
+        .insert(Swing {
+            timer: Timer::from_seconds(1. / speed, TimerMode::Once),
+            start_angle,
+            end_angle,
+            damage,
+            knockback,
+            crit_chance,
+            hit: Vec::new(),
+        })
+        .insert(Name::new(format!("Swing({})", item_def.name)))
+        .insert(WorldEntity)
+        .insert(Collider::cuboid(SWORD_REACH / 2., SWORD_WIDTH / 2.))
+        .insert(Sensor)
+        .insert(WEAPON_COLLIDE_WITH_ENEMIES)
+        .with_children(|parent| {
+            // the icon shows the blade along its diagonal, pointing up and right
+            parent.spawn(SpriteSheetBundle {
+                sprite: TextureAtlasSprite {
+                    index: item_def.icon,
+                    custom_size: Some(Vec2::splat(SWORD_REACH / SQRT_2)),
+                    ..default()
+                },
+                texture_atlas: item_sprites.0.clone(),
+                transform: Transform::from_rotation(Quat::from_rotation_z(-FRAC_PI_4)),
+                ..default()
+            });
+        });
+}
+
+// the swing follows the player while it sweeps through its arc
+pub fn update_swings(
+    mut commands: Commands,
+    time: Res<Time>,
+    player_q: Query<&Transform, (With<Player>, Without<Swing>)>,
+    mut swing_q: Query<(Entity, &mut Swing, &mut Transform)>,
+) {
+    let Ok(player_transform) = player_q.get_single() else {
+        return;
+    };
+    let player_pos = player_transform.translation.truncate();
+    for (entity, mut swing, mut transform) in swing_q.iter_mut() {
+        if swing.timer.tick(time.delta()).finished() {
+            commands.entity(entity).despawn_recursive();
+            continue;
+        }
+        *transform = swing_transform(player_pos, swing.angle());
+    }
+}
+
+pub fn swing_hits(
+    rapier_context: Res<RapierContext>,
+    player_q: Query<&Transform, With<Player>>,
+    target_q: Query<&Transform, (With<Health>, Without<Player>)>,
+    mut swing_q: Query<(Entity, &mut Swing)>,
+    mut damage_events: EventWriter<DamageEvent>,
+) {
+    let Ok(player_transform) = player_q.get_single() else {
+        return;
+    };
+    let mut rng = thread_rng();
+    for (entity, mut swing) in swing_q.iter_mut() {
+        for (collider1, collider2, intersecting) in rapier_context.intersections_with(entity) {
+            let target = if collider1 == entity {
+                collider2
+            } else {
+                collider1
+            };
+            if !intersecting || swing.hit.contains(&target) {
+                continue;
+            }
+            let Ok(target_transform) = target_q.get(target) else {
+                continue;
+            };
+            swing.hit.push(target);
+
+            let critical = rng.gen_bool(swing.crit_chance.clamp(0., 1.) as f64);
+            let multiplier = if critical { CRIT_MULTIPLIER } else { 1. };
+            let away = (target_transform.translation.x - player_transform.translation.x).signum();
+            damage_events.send(DamageEvent {
+                target,
+                amount: swing.damage * multiplier,
+                source: DamageSource::Melee,
+                knockback: Vec2::new(away, 0.5) * swing.knockback,
+                critical,
+            });
+        }
+    }
+}
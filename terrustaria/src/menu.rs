@@ -0,0 +1,178 @@
+use bevy::app::AppExit;
+use bevy::prelude::*;
+
+use crate::constants::{hud::*, menu::*};
+use crate::game_state::AppState;
+
+// renders the menus, the player has its own camera in the game
+#[derive(Component)]
+pub struct MenuCamera;
+
+// root node of the screen shown in the current state
+#[derive(Component)]
+pub struct MenuRoot;
+
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MenuButton {
+    Play,
+    Resume,
+    Settings,
+    SaveAndQuit,
+    Back,
+    Quit,
+}
+
+fn spawn_menu(
+    commands: &mut Commands,
+    asset_server: &AssetServer,
+    background: Color,
+    title: &str,
+    lines: &[&str],
+    buttons: &[(&str, MenuButton)],
+) {
+    let font = asset_server.load(FONT);
+    let text_style = TextStyle {
+        font: font.clone(),
+        font_size: MENU_FONT_SIZE,
+        color: Color::WHITE,
+    };
+
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.), Val::Percent(100.)),
+                flex_direction: FlexDirection::Column,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            background_color: background.into(),
+            ..default()
+        })
+        .insert(MenuRoot)
+        .insert(Name::new("Menu"))
+        .with_children(|parent| {
+            parent.spawn(
+                TextBundle::from_section(
+                    title,
+                    TextStyle {
+                        font,
+                        font_size: TITLE_FONT_SIZE,
+                        color: Color::WHITE,
+                    },
+                )
+                .with_style(Style {
+                    margin: UiRect::bottom(Val::Px(MENU_GAP * 2.)),
+                    ..default()
+                }),
+            );
+            for line in lines {
+                parent.spawn(
+                    TextBundle::from_section(*line, text_style.clone()).with_style(Style {
+                        margin: UiRect::bottom(Val::Px(MENU_GAP)),
+                        ..default()
+                    }),
+                );
+            }
+            for (label, button) in buttons {
+                parent
+                    .spawn(ButtonBundle {
+                        style: Style {
+                            size: Size::new(Val::Px(MENU_BUTTON_WIDTH), Val::Auto),
+                            margin: UiRect::bottom(Val::Px(MENU_GAP)),
+                            padding: UiRect::all(Val::Px(PANEL_PADDING)),
+                            justify_content: JustifyContent::Center,
+                            ..default()
+                        },
+                        background_color: BUTTON_COLOR.into(),
+                        ..default()
+                    })
+                    .insert(*button)
+                    .with_children(|button_parent| {
+                        button_parent.spawn(TextBundle::from_section(*label, text_style.clone()));
+                    });
+            }
+        });
+}
+
+pub fn spawn_menu_camera(mut commands: Commands, camera_q: Query<(), With<MenuCamera>>) {
+    // going back from the world selection keeps the camera of the main menu
+    if camera_q.is_empty() {
+        commands.spawn((Camera2dBundle::default(), MenuCamera));
+    }
+}
+
+pub fn despawn_menu_camera(mut commands: Commands, camera_q: Query<Entity, With<MenuCamera>>) {
+    for camera in camera_q.iter() {
+        commands.entity(camera).despawn_recursive();
+    }
+}
+
+pub fn spawn_main_menu(mut commands: Commands, asset_server: Res<AssetServer>) {
+    spawn_menu(
+        &mut commands,
+        &asset_server,
+        MENU_BACKGROUND,
+        "Terrustaria",
+        &[],
+        &[
+            ("Play", MenuButton::Play),
+            ("Settings", MenuButton::Settings),
+            ("Quit", MenuButton::Quit),
+        ],
+    );
+}
+
+pub fn spawn_pause_overlay(mut commands: Commands, asset_server: Res<AssetServer>) {
+    spawn_menu(
+        &mut commands,
+        &asset_server,
+        OVERLAY_COLOR,
+        "Paused",
+        &[],
+        &[
+            ("Resume", MenuButton::Resume),
+            ("Settings", MenuButton::Settings),
+            ("Save & Quit to Menu", MenuButton::SaveAndQuit),
+            ("Quit", MenuButton::Quit),
+        ],
+    );
+}
+
+pub fn despawn_menu(mut commands: Commands, menu_q: Query<Entity, With<MenuRoot>>) {
+    for menu in menu_q.iter() {
+        commands.entity(menu).despawn_recursive();
+    }
+}
+
+pub fn handle_menu_buttons(
+    mut interaction_q: Query<
+        (&Interaction, &MenuButton, &mut BackgroundColor),
+        Changed<Interaction>,
+    >,
+    mut state: ResMut<State<AppState>>,
+    mut exit: EventWriter<AppExit>,
+) {
+    for (interaction, button, mut color) in interaction_q.iter_mut() {
+        match interaction {
+            Interaction::Hovered => *color = HOVERED_BUTTON_COLOR.into(),
+            Interaction::None => *color = BUTTON_COLOR.into(),
+            Interaction::Clicked => {
+                let result = match button {
+                    MenuButton::Play => state.set(AppState::WorldSelect),
+                    MenuButton::Resume | MenuButton::Back => state.pop(),
+                    MenuButton::Settings => state.push(AppState::Settings),
+                    // the world has to be saved first, see save::save_and_quit_to_menu
+                    MenuButton::SaveAndQuit => Ok(()),
+                    MenuButton::Quit => {
+                        exit.send(AppExit);
+                        Ok(())
+                    }
+                };
+                if let Err(err) = result {
+                    warn!("cannot leave the menu: {err:?}");
+                }
+            }
+        }
+    }
+}
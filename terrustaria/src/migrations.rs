@@ -0,0 +1,294 @@
+use bevy_ecs_tilemap::prelude::TilemapSize;
+use serde::{Deserialize, Serialize};
+
+use crate::constants::save::{PLAYER_VERSION, WORLD_VERSION};
+use crate::item::ItemId;
+use crate::save::{ObjectSave, SaveError, WorldFile, WorldSave};
+use crate::save_chunks::encode_world;
+
+// upgrades the text of a save by exactly one version
+pub type Migration = fn(&str) -> Result<String, SaveError>;
+
+// the n-th migration upgrades a save of version n + 1; every step reads and writes the
+// formats frozen below, only the last one may produce the live types and it has to be
+// frozen as well before the format changes again
+pub const WORLD_MIGRATIONS: [Migration; (WORLD_VERSION - 1) as usize] =
+    [world_v1_to_v2, world_v2_to_v3];
+pub const PLAYER_MIGRATIONS: [Migration; (PLAYER_VERSION - 1) as usize] = [player_v1_to_v2];
+
+// only the version is read before the save gets migrated, the rest is skipped
+#[derive(Deserialize)]
+struct Versioned {
+    // saves from before the versioning have no version at all
+    #[serde(default = "first_version")]
+    version: u32,
+}
+
+fn first_version() -> u32 {
+    1
+}
+
+// the objects and the inventory have not changed since version 1
+#[derive(Serialize, Deserialize)]
+struct ObjectSaveV1 {
+    item: String,
+    x: u32,
+    y: u32,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ItemStackV1 {
+    item: String,
+    count: u32,
+}
+
+#[derive(Serialize, Deserialize)]
+struct InventoryV1 {
+    slots: Vec<Option<ItemStackV1>>,
+}
+
+// version 1 kept texture indices, which broke whenever the atlas was reordered
+#[derive(Deserialize)]
+struct WorldSaveV1 {
+    width: u32,
+    height: u32,
+    tiles: Vec<Option<u32>>,
+    walls: Vec<Option<u32>>,
+    explored: Vec<bool>,
+    objects: Vec<ObjectSaveV1>,
+}
+
+#[derive(Deserialize)]
+struct PlayerSaveV1 {
+    position: (f32, f32),
+    inventory: InventoryV1,
+    hotbar_slot: usize,
+}
+
+// version 2 kept one value per cell of every layer, which grew huge with the size of the map
+#[derive(Serialize, Deserialize)]
+struct WorldSaveV2 {
+    version: u32,
+    width: u32,
+    height: u32,
+    tiles: Vec<Option<String>>,
+    walls: Vec<Option<String>>,
+    explored: Vec<bool>,
+    objects: Vec<ObjectSaveV1>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct PlayerSaveV2 {
+    version: u32,
+    position: (f32, f32),
+    inventory: InventoryV1,
+    hotbar_slot: usize,
+}
+
+// the atlas as it was laid out when version 1 was written, it must not follow constants::offsets
+const V1_TILES: [(u32, &str); 4] = [(0, "Dirt"), (5, "Stone"), (10, "Water"), (15, "Diamond")];
+const V1_WALLS: [(u32, &str); 1] = [(3, "DirtWall")];
+
+fn v1_key(keys: &[(u32, &str)], texture_index: Option<u32>) -> Option<String> {
+    let texture_index = texture_index?;
+    keys.iter()
+        .find(|(index, _)| *index == texture_index)
+        .map(|(_, name)| String::from(*name))
+}
+
+fn world_v1_to_v2(data: &str) -> Result<String, SaveError> {
+    let old: WorldSaveV1 = ron::from_str(data)?;
+    let new = WorldSaveV2 {
+        version: 2,
+        width: old.width,
+        height: old.height,
+        tiles: old
+            .tiles
+            .into_iter()
+            .map(|tile| v1_key(&V1_TILES, tile))
+            .collect(),
+        walls: old
+            .walls
+            .into_iter()
+            .map(|wall| v1_key(&V1_WALLS, wall))
+            .collect(),
+        explored: old.explored,
+        objects: old.objects,
+    };
+    Ok(ron::to_string(&new)?)
+}
+
+// only the version was added to the player file
+fn player_v1_to_v2(data: &str) -> Result<String, SaveError> {
+    let old: PlayerSaveV1 = ron::from_str(data)?;
+    let new = PlayerSaveV2 {
+        version: 2,
+        position: old.position,
+        inventory: old.inventory,
+        hotbar_slot: old.hotbar_slot,
+    };
+    Ok(ron::to_string(&new)?)
+}
+
+// the layers are split into compressed chunks, which stay in the world file until
+// the next save writes them into their own files
+fn world_v2_to_v3(data: &str) -> Result<String, SaveError> {
+    let old: WorldSaveV2 = ron::from_str(data)?;
+    let world = WorldSave {
+        width: old.width,
+        height: old.height,
+        tiles: old.tiles,
+        walls: old.walls,
+        explored: old.explored,
+        objects: old
+            .objects
+            .into_iter()
+            .map(|object| ObjectSave {
+                item: ItemId(object.item),
+                x: object.x,
+                y: object.y,
+            })
+            .collect(),
+        drops: Vec::new(),
+    };
+    if !world.fits(&TilemapSize {
+        x: world.width,
+        y: world.height,
+    }) {
+        return Err(SaveError::Corrupted);
+    }
+    let (header, chunks) = encode_world(&world)?;
+    let new = WorldFile { chunks, ..header };
+    Ok(ron::to_string(&new)?)
+}
+
+pub fn save_version(data: &str) -> Result<u32, SaveError> {
+    let versioned: Versioned = ron::from_str(data)?;
+    Ok(versioned.version)
+}
+
+// runs the migrations one after another until the save reaches the version after the last one
+pub fn migrate(data: String, migrations: &[Migration]) -> Result<String, SaveError> {
+    let mut data = data;
+    let mut version = save_version(&data)?;
+    let current = migrations.len() as u32 + 1;
+    if version == 0 || version > current {
+        return Err(SaveError::UnsupportedVersion(version));
+    }
+    while version < current {
+        data = migrations[(version - 1) as usize](&data)?;
+        version += 1;
+    }
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::inventory::Inventory;
+    use crate::save::PlayerSave;
+    use crate::save_chunks::decode_world;
+
+    // the same 2x2 world in every version, the bottom row is ground
+    fn expected_world() -> WorldSave {
+        let name = |name: &str| Some(String::from(name));
+        WorldSave {
+            width: 2,
+            height: 2,
+            tiles: vec![name("Dirt"), name("Stone"), None, name("Diamond")],
+            walls: vec![name("DirtWall"), name("DirtWall"), None, None],
+            explored: vec![true, false, true, false],
+            objects: vec![ObjectSave {
+                item: ItemId::new("workbench"),
+                x: 0,
+                y: 1,
+            }],
+            drops: Vec::new(),
+        }
+    }
+
+    fn load_world(data: &str) -> WorldSave {
+        let migrated = migrate(String::from(data), &WORLD_MIGRATIONS).unwrap();
+        let file: WorldFile = ron::from_str(&migrated).unwrap();
+        assert_eq!(file.version, WORLD_VERSION);
+        decode_world(&file, &file.chunks).unwrap()
+    }
+
+    fn expected_inventory() -> Inventory {
+        ron::from_str(r#"(slots: [Some((item: "dirt", count: 3)), None])"#).unwrap()
+    }
+
+    #[test]
+    fn world_from_v1() {
+        let v1 = r#"(
+            width: 2,
+            height: 2,
+            tiles: [Some(0), Some(5), None, Some(15)],
+            walls: [Some(3), Some(3), None, None],
+            explored: [true, false, true, false],
+            objects: [(item: "workbench", x: 0, y: 1)],
+        )"#;
+        assert_eq!(load_world(v1), expected_world());
+    }
+
+    #[test]
+    fn world_from_v2() {
+        let v2 = r#"(
+            version: 2,
+            width: 2,
+            height: 2,
+            tiles: [Some("Dirt"), Some("Stone"), None, Some("Diamond")],
+            walls: [Some("DirtWall"), Some("DirtWall"), None, None],
+            explored: [true, false, true, false],
+            objects: [(item: "workbench", x: 0, y: 1)],
+        )"#;
+        assert_eq!(load_world(v2), expected_world());
+    }
+
+    #[test]
+    fn player_from_v1() {
+        let v1 = r#"(
+            position: (10.0, -20.0),
+            inventory: (slots: [Some((item: "dirt", count: 3)), None]),
+            hotbar_slot: 1,
+        )"#;
+        let migrated = migrate(String::from(v1), &PLAYER_MIGRATIONS).unwrap();
+        let player: PlayerSave = ron::from_str(&migrated).unwrap();
+        assert_eq!(
+            player,
+            PlayerSave {
+                version: PLAYER_VERSION,
+                position: (10., -20.),
+                inventory: expected_inventory(),
+                hotbar_slot: 1,
+                world: None,
+                health: None,
+                bed: None,
+            }
+        );
+    }
+
+    #[test]
+    fn player_from_v2() {
+        let v2 = r#"(
+            version: 2,
+            position: (10.0, -20.0),
+            inventory: (slots: [Some((item: "dirt", count: 3)), None]),
+            hotbar_slot: 1,
+        )"#;
+        let migrated = migrate(String::from(v2), &PLAYER_MIGRATIONS).unwrap();
+        let player: PlayerSave = ron::from_str(&migrated).unwrap();
+        assert_eq!(player.version, PLAYER_VERSION);
+        assert_eq!(player.position, (10., -20.));
+        assert_eq!(player.inventory, expected_inventory());
+        assert_eq!(player.hotbar_slot, 1);
+    }
+
+    #[test]
+    fn newer_save_is_rejected() {
+        assert!(matches!(
+            migrate(String::from("(version: 99)"), &WORLD_MIGRATIONS),
+            Err(SaveError::UnsupportedVersion(99))
+        ));
+    }
+}
@@ -0,0 +1,152 @@
+use crate::actions::{Action, Actions};
+use crate::constants::map::TILE_SIZE;
+use crate::constants::player::{PLACE_RANGE, PLAYER_HALF_SIZE};
+use crate::cursor::{cursor_in_map_pos, CursorPos, UiPointer};
+use crate::hotbar::Hotbar;
+use crate::inventory::Inventory;
+use crate::item::{ItemKind, ItemRegistry};
+use crate::map::{TileChanged, WithColliders};
+use crate::player::Player;
+use crate::stations::{spawn_station, station_fits, StationMap, StationSprites};
+use crate::tile::TileCollection;
+use bevy::prelude::*;
+use bevy_ecs_tilemap::prelude::*;
+
+// the player would get stuck inside of a tile placed over him
+pub fn overlaps_player(tile_center: Vec2, player_pos: Vec2) -> bool {
+    let diff = (tile_center - player_pos).abs();
+    diff.x < TILE_SIZE.x / 2. + PLAYER_HALF_SIZE && diff.y < TILE_SIZE.y / 2. + PLAYER_HALF_SIZE
+}
+
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+pub fn place_tile_after_click(
+    mut commands: Commands,
+    cursor_pos: Res<CursorPos>,
+    mut tilemap_q: Query<
+        (
+            Entity,
+            &TilemapSize,
+            &TilemapGridSize,
+            &TilemapType,
+            &mut TileStorage,
+            &Transform,
+        ),
+        With<WithColliders>,
+    >,
+    tile_types: Res<TileCollection>,
+    registry: Res<ItemRegistry>,
+    station_sprites: Res<StationSprites>,
+    mut station_map: ResMut<StationMap>,
+    mut player_q: Query<
+        (&Transform, &Hotbar, &mut Inventory),
+        (With<Player>, Without<WithColliders>),
+    >,
+    actions: Actions,
+    ui_pointer: UiPointer,
+    mut tile_changes: EventWriter<TileChanged>,
+) {
+    // a selected block or station is placed by interacting, once per press
+    if !actions.just_pressed(Action::Interact) {
+        return;
+    }
+    if ui_pointer.over_ui() {
+        return;
+    }
+
+    // we have only one player
+    let (player_transform, hotbar, mut inventory) = player_q.single_mut();
+    let player_pos = player_transform.translation.truncate();
+    let Some(item_def) = hotbar.selected_item(&inventory, &registry) else {
+        return;
+    };
+    // blocks take a single cell, stations grow from it to the right and upwards
+    let size = match &item_def.kind {
+        ItemKind::Block { .. } => UVec2::ONE,
+        ItemKind::Station { width, height, .. } => UVec2::new(*width, *height),
+        _ => return,
+    };
+
+    for (tilemap_entity, map_size, grid_size, map_type, mut tile_storage, map_transform) in
+        tilemap_q.iter_mut()
+    {
+        let cursor_in_map_pos = cursor_in_map_pos(&cursor_pos, map_transform);
+        let Some(tile_pos) =
+            TilePos::from_world_pos(&cursor_in_map_pos, map_size, grid_size, map_type)
+        else {
+            continue;
+        };
+        if !station_fits(tile_pos, size, map_size) {
+            continue;
+        }
+
+        // check if player is in range of the cells and not in their way
+        let origin_center =
+            tile_pos.center_in_world(grid_size, map_type) + map_transform.translation.truncate();
+        let diff = (origin_center - player_pos).abs();
+        if diff.x > PLACE_RANGE * TILE_SIZE.x || diff.y > PLACE_RANGE * TILE_SIZE.y {
+            continue;
+        }
+
+        let cells: Vec<TilePos> = (0..size.x)
+            .flat_map(|dx| {
+                (0..size.y).map(move |dy| TilePos {
+                    x: tile_pos.x + dx,
+                    y: tile_pos.y + dy,
+                })
+            })
+            .collect();
+        let blocked = cells.iter().any(|cell| {
+            let cell_center =
+                cell.center_in_world(grid_size, map_type) + map_transform.translation.truncate();
+            tile_storage.get(cell).is_some()
+                || station_map.get(cell).is_some()
+                || overlaps_player(cell_center, player_pos)
+        });
+        if blocked {
+            continue;
+        }
+
+        match &item_def.kind {
+            ItemKind::Block { tile } => {
+                let Some(tile_type) = tile_types.by_name(tile) else {
+                    return;
+                };
+                if inventory.remove(&item_def.id, 1) > 0 {
+                    return;
+                }
+
+                let tile_entity = commands
+                    .spawn(TileBundle {
+                        position: tile_pos,
+                        tilemap_id: TilemapId(tilemap_entity),
+                        texture_index: tile_type.get_texture_index(),
+                        ..Default::default()
+                    })
+                    .insert(Name::new(format!(
+                        "ForegroundTile({},{})",
+                        tile_pos.x, tile_pos.y
+                    )))
+                    .id();
+                tile_storage.set(&tile_pos, tile_entity);
+                tile_changes.send(TileChanged(tile_pos));
+            }
+            ItemKind::Station { sprite, .. } => {
+                if inventory.remove(&item_def.id, 1) > 0 {
+                    return;
+                }
+
+                spawn_station(
+                    &mut commands,
+                    &station_sprites,
+                    &mut station_map,
+                    map_size,
+                    item_def.id.clone(),
+                    *sprite,
+                    tile_pos,
+                    size,
+                );
+            }
+            _ => return,
+        }
+    }
+}
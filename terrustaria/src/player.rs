@@ -1,32 +1,71 @@
 use bevy::prelude::*;
+use bevy_ecs_tilemap::prelude::TilePos;
 use bevy_rapier2d::prelude::*;
 
+use crate::actions::{Action, Actions};
 use crate::constants::{
-    collision_groups::PLAYER_COLLIDE_WITH_ALL, depth::*, player::*, world::GRAVITY,
+    collision_groups::PLAYER_COLLIDE_WITH_ALL, depth::*, health::PLAYER_MAX_HEALTH, player::*,
+    world::GRAVITY,
 };
+use crate::death::SpawnPoint;
+use crate::destroy_tiles::Miner;
+use crate::game_state::WorldEntity;
+use crate::health::{FallTracker, Health};
+use crate::hotbar::Hotbar;
+use crate::inventory::Inventory;
+use crate::item::{ItemId, ItemRegistry};
+use crate::projectiles::Shooter;
+use crate::save::PlayerSave;
 
 #[derive(Component)]
 pub struct Player {
     // linear speed in meters per second
     movement_speed: f32,
 }
+
+impl Default for Player {
+    fn default() -> Self {
+        Self {
+            movement_speed: MOVEMENT_SPEED,
+        }
+    }
+}
+
 #[derive(Component)]
 pub struct MainCamera;
 
+// anything jumping off the ground, it can jump again once it touches something
 #[derive(Component)]
 pub struct Jumper {
     jump_impulse: f32,
     is_jumping: bool,
 }
 
+impl Jumper {
+    pub fn new(jump_impulse: f32) -> Self {
+        Self {
+            jump_impulse,
+            is_jumping: false,
+        }
+    }
+
+    pub fn is_jumping(&self) -> bool {
+        self.is_jumping
+    }
+
+    pub fn jump(&mut self, velocity: &mut Velocity, horizontal_speed: f32) {
+        velocity.linvel = Vec2::new(horizontal_speed, self.jump_impulse);
+        self.is_jumping = true;
+    }
+}
+
 pub fn player_jump(
-    keyboard_input: Res<Input<KeyCode>>,
+    actions: Actions,
     mut players: Query<(&mut Jumper, &mut Velocity), With<Player>>,
 ) {
     for (mut jumper, mut velocity) in players.iter_mut() {
-        if keyboard_input.pressed(KeyCode::Space) && !jumper.is_jumping {
-            velocity.linvel = Vec2::new(0., jumper.jump_impulse);
-            jumper.is_jumping = true
+        if actions.pressed(Action::Jump) && !jumper.is_jumping {
+            jumper.jump(&mut velocity, 0.);
         }
     }
 }
@@ -54,44 +93,85 @@ fn set_jumping_false_if_touching_floor(
     }
 }
 
-pub fn spawn_player(mut commands: Commands, asset_server: Res<AssetServer>) {
+pub fn spawn_player(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    registry: Res<ItemRegistry>,
+    saved: Option<Res<PlayerSave>>,
+) {
     let player_handle: Handle<Image> = asset_server.load("player.png");
+    let (position, inventory, hotbar, health, spawn_point) = match saved {
+        Some(saved) => (
+            Vec2::new(saved.position.0, saved.position.1),
+            saved.inventory.clone(),
+            Hotbar::new(saved.hotbar_slot),
+            // a character saved on the death screen comes back to life
+            saved
+                .health
+                .filter(|current| *current > 0.)
+                .map_or(Health::new(PLAYER_MAX_HEALTH), |current| {
+                    Health::with_current(current, PLAYER_MAX_HEALTH)
+                }),
+            SpawnPoint {
+                bed: saved.bed.map(|(x, y)| TilePos { x, y }),
+            },
+        ),
+        None => {
+            let mut inventory = Inventory::default();
+            for (item, count) in STARTING_ITEMS {
+                inventory.add(&registry, &ItemId::new(item), count);
+            }
+            (
+                Vec2::new(SPAWN_POSITION.0, SPAWN_POSITION.1),
+                inventory,
+                Hotbar::default(),
+                Health::new(PLAYER_MAX_HEALTH),
+                SpawnPoint::default(),
+            )
+        }
+    };
+
     commands
         .spawn((
             SpriteBundle {
                 texture: player_handle,
-                transform: Transform::from_xyz(0., 50., Z_PLAYER),
+                transform: Transform::from_translation(position.extend(Z_PLAYER)),
                 ..default()
             },
-            Player {
-                movement_speed: MOVEMENT_SPEED, // metres per second
-            },
+            Player::default(),
         ))
-        .insert(Jumper {
-            jump_impulse: JUMP_POWER,
-            is_jumping: false,
-        })
+        .insert(Jumper::new(JUMP_POWER))
+        .insert(Miner::default())
+        .insert(Shooter::default())
+        .insert(inventory)
+        .insert(hotbar)
+        .insert(health)
+        .insert(FallTracker::default())
+        .insert(spawn_point)
         .insert(Name::new("Player"))
+        .insert(WorldEntity)
         .insert(RigidBody::Dynamic)
         .insert(LockedAxes::ROTATION_LOCKED)
-        .insert(Collider::cuboid(8., 8.))
+        .insert(Collider::cuboid(PLAYER_HALF_SIZE, PLAYER_HALF_SIZE))
         .insert(PLAYER_COLLIDE_WITH_ALL)
         .insert(GravityScale(GRAVITY))
         .insert(Velocity::zero())
         .with_children(|parent| {
-            parent.spawn((Camera2dBundle::new_with_far(100.), MainCamera));
+            // visibility lets the hud attached to the camera be drawn
+            parent.spawn((
+                Camera2dBundle::new_with_far(100.),
+                VisibilityBundle::default(),
+                MainCamera,
+            ));
         });
 }
 
-pub fn player_movement(
-    keyboard_input: Res<Input<KeyCode>>,
-    mut players: Query<(&Player, &mut Velocity)>,
-) {
+pub fn player_movement(actions: Actions, mut players: Query<(&Player, &mut Velocity)>) {
     for (player, mut velocity) in players.iter_mut() {
-        if keyboard_input.pressed(KeyCode::A) {
+        if actions.pressed(Action::MoveLeft) {
             velocity.linvel = Vec2::new(-player.movement_speed, velocity.linvel.y);
         }
-        if keyboard_input.pressed(KeyCode::D) {
+        if actions.pressed(Action::MoveRight) {
             velocity.linvel = Vec2::new(player.movement_speed, velocity.linvel.y);
         }
     }
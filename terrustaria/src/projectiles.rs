@@ -0,0 +1,363 @@
+use std::f32::consts::FRAC_PI_4;
+
+use bevy::prelude::*;
+use bevy_ecs_tilemap::prelude::*;
+use bevy_rapier2d::prelude::*;
+
+use crate::actions::{Action, Actions};
+use crate::colliders::MapCollider;
+use crate::constants::{
+    collision_groups::PROJECTILE_COLLIDE_WITH_MAP_AND_ENEMIES,
+    combat::*,
+    depth::Z_PROJECTILES,
+    map::{GRID_SIZE, MAP_TYPE},
+    player::PLAYER_HALF_SIZE,
+    world::GRAVITY,
+};
+use crate::cursor::{CursorPos, UiPointer};
+use crate::game_state::WorldEntity;
+use crate::health::{DamageEvent, DamageSource, Health};
+use crate::hotbar::Hotbar;
+use crate::inventory::Inventory;
+use crate::item::{ItemKind, ItemRegistry, ItemSprites, ProjectileEffect};
+use crate::map::{remove_foreground_tile, TileChanged, TileDestroyed, WithColliders};
+use crate::place_tiles::overlaps_player;
+use crate::player::Player;
+use crate::stations::StationMap;
+use crate::tile::TileCollection;
+
+// time until the next shot of a bow or throw
+#[derive(Component, Default)]
+pub struct Shooter {
+    cooldown: Timer,
+}
+
+#[derive(Component)]
+pub struct Projectile {
+    damage: f32,
+    effect: ProjectileEffect,
+    lifetime: Timer,
+    // hit something already, later contacts are ignored
+    landed: bool,
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn fire_projectiles(
+    mut commands: Commands,
+    time: Res<Time>,
+    actions: Actions,
+    ui_pointer: UiPointer,
+    cursor_pos: Res<CursorPos>,
+    registry: Res<ItemRegistry>,
+    item_sprites: Res<ItemSprites>,
+    mut player_q: Query<(&Transform, &Hotbar, &mut Inventory, &mut Shooter), With<Player>>,
+) {
+    let Ok((player_transform, hotbar, mut inventory, mut shooter)) = player_q.get_single_mut()
+    else {
+        return;
+    };
+    shooter.cooldown.tick(time.delta());
+    if !actions.pressed(Action::UseItem) || ui_pointer.over_ui() || !shooter.cooldown.finished() {
+        return;
+    }
+    let Some(item_def) = hotbar.selected_item(&inventory, &registry) else {
+        return;
+    };
+    let (damage, speed, launch_speed, ammo, effect) = match &item_def.kind {
+        ItemKind::Bow {
+            damage,
+            speed,
+            launch_speed,
+            ammo,
+        } => (
+            *damage,
+            *speed,
+            *launch_speed,
+            ammo.clone(),
+            ProjectileEffect::None,
+        ),
+        ItemKind::Thrown {
+            damage,
+            speed,
+            launch_speed,
+            effect,
+        } => (
+            *damage,
+            *speed,
+            *launch_speed,
+            item_def.id.clone(),
+            effect.clone(),
+        ),
+        _ => return,
+    };
+    let Some(ammo_def) = registry.get(&ammo) else {
+        return;
+    };
+    // nothing happens without ammo
+    if inventory.remove(&ammo, 1) > 0 {
+        return;
+    }
+    shooter.cooldown = Timer::from_seconds(1. / speed, TimerMode::Once);
+
+    let player_pos = player_transform.translation.truncate();
+    let direction = (cursor_pos.0.truncate() - player_pos)
+        .try_normalize()
+        .unwrap_or(Vec2::X);
+    let position = player_pos + direction * (PLAYER_HALF_SIZE + PROJECTILE_RADIUS);
+    commands
+        .spawn(SpriteSheetBundle {
+            sprite: TextureAtlasSprite {
+                index: ammo_def.icon,
+                custom_size: Some(Vec2::splat(PROJECTILE_SIZE)),
+                ..default()
+            },
+            texture_atlas: item_sprites.0.clone(),
+            transform: Transform::from_translation(position.extend(Z_PROJECTILES)),
+            ..default()
+        })
+        .insert(Projectile {
+            damage,
+            effect,
+            lifetime: Timer::from_seconds(PROJECTILE_LIFETIME, TimerMode::Once),
+            landed: false,
+        })
+        .insert(Name::new(format!("Projectile({})", ammo_def.name)))
+        .insert(WorldEntity)
+        .insert(RigidBody::Dynamic)
+        .insert(Collider::ball(PROJECTILE_RADIUS))
+        // stopped by hand when it hits something, instead of bouncing off
+        .insert(Sensor)
+        .insert(PROJECTILE_COLLIDE_WITH_MAP_AND_ENEMIES)
+        .insert(ActiveEvents::COLLISION_EVENTS)
+        .insert(GravityScale(GRAVITY))
+        .insert(Velocity::linear(direction * launch_speed));
+}
+
+// icons point up and to the right, projectiles point where they fly
+pub fn orient_projectiles(mut projectile_q: Query<(&Projectile, &Velocity, &mut Transform)>) {
+    for (projectile, velocity, mut transform) in projectile_q.iter_mut() {
+        if projectile.landed || velocity.linvel == Vec2::ZERO {
+            continue;
+        }
+        let angle = velocity.linvel.y.atan2(velocity.linvel.x);
+        transform.rotation = Quat::from_rotation_z(angle - FRAC_PI_4);
+    }
+}
+
+fn cells_around(center: TilePos, radius: u32, map_size: TilemapSize) -> Vec<TilePos> {
+    let radius = radius as i64;
+    let mut cells = Vec::new();
+    for dx in -radius..=radius {
+        for dy in -radius..=radius {
+            let (x, y) = (center.x as i64 + dx, center.y as i64 + dy);
+            if dx * dx + dy * dy > radius * radius
+                || x < 0
+                || y < 0
+                || x >= map_size.x as i64
+                || y >= map_size.y as i64
+            {
+                continue;
+            }
+            cells.push(TilePos {
+                x: x as u32,
+                y: y as u32,
+            });
+        }
+    }
+    cells
+}
+
+#[allow(clippy::too_many_arguments)]
+fn explode(
+    commands: &mut Commands,
+    tile_storage: &mut TileStorage,
+    cells: &[TilePos],
+    power: u32,
+    tile_q: &Query<&TileTextureIndex>,
+    tile_types: &TileCollection,
+    tile_changes: &mut EventWriter<TileChanged>,
+    destroyed_tiles: &mut EventWriter<TileDestroyed>,
+) {
+    for cell in cells {
+        let Some(tile_entity) = tile_storage.get(cell) else {
+            continue;
+        };
+        let Ok(texture_index) = tile_q.get(tile_entity) else {
+            continue;
+        };
+        let min_power = tile_types
+            .by_texture_index(*texture_index)
+            .map_or(0, |tile_type| tile_type.get_min_power());
+        if min_power > power {
+            continue;
+        }
+        remove_foreground_tile(
+            commands,
+            tile_storage,
+            *cell,
+            *texture_index,
+            tile_changes,
+            destroyed_tiles,
+        );
+    }
+}
+
+// fills the empty cells, the player is never walled in
+#[allow(clippy::too_many_arguments)]
+fn fill(
+    commands: &mut Commands,
+    tilemap: Entity,
+    tile_storage: &mut TileStorage,
+    station_map: &StationMap,
+    cells: &[TilePos],
+    texture_index: TileTextureIndex,
+    map_offset: Vec2,
+    player_pos: Option<Vec2>,
+    tile_changes: &mut EventWriter<TileChanged>,
+) {
+    for cell in cells {
+        let cell_center = cell.center_in_world(&GRID_SIZE, &MAP_TYPE) + map_offset;
+        let blocked = tile_storage.get(cell).is_some()
+            || station_map.get(cell).is_some()
+            || player_pos.map_or(false, |player_pos| overlaps_player(cell_center, player_pos));
+        if blocked {
+            continue;
+        }
+        let tile_entity = commands
+            .spawn(TileBundle {
+                position: *cell,
+                tilemap_id: TilemapId(tilemap),
+                texture_index,
+                ..Default::default()
+            })
+            .insert(Name::new(format!("ForegroundTile({},{})", cell.x, cell.y)))
+            .id();
+        tile_storage.set(cell, tile_entity);
+        tile_changes.send(TileChanged(*cell));
+    }
+}
+
+// enemies take the damage, tiles take the effect, arrows stick in the ground
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+pub fn projectile_hits(
+    mut commands: Commands,
+    mut collision_events: EventReader<CollisionEvent>,
+    mut projectile_q: Query<(&Transform, &Velocity, &mut Projectile)>,
+    enemy_q: Query<(), (With<Health>, Without<Player>)>,
+    map_collider_q: Query<(), With<MapCollider>>,
+    mut tilemap_q: Query<(Entity, &mut TileStorage, &Transform), With<WithColliders>>,
+    tile_q: Query<&TileTextureIndex>,
+    station_map: Res<StationMap>,
+    tile_types: Res<TileCollection>,
+    player_q: Query<&Transform, With<Player>>,
+    mut damage_events: EventWriter<DamageEvent>,
+    mut tile_changes: EventWriter<TileChanged>,
+    mut destroyed_tiles: EventWriter<TileDestroyed>,
+) {
+    let player_pos = player_q
+        .get_single()
+        .ok()
+        .map(|transform| transform.translation.truncate());
+    for collision_event in collision_events.iter() {
+        let CollisionEvent::Started(h1, h2, ..) = collision_event else {
+            continue;
+        };
+        for (entity, other) in [(*h1, *h2), (*h2, *h1)] {
+            let Ok((transform, velocity, mut projectile)) = projectile_q.get_mut(entity) else {
+                continue;
+            };
+            let hit_enemy = enemy_q.contains(other);
+            if projectile.landed || (!hit_enemy && !map_collider_q.contains(other)) {
+                continue;
+            }
+            projectile.landed = true;
+
+            if hit_enemy {
+                let direction = velocity.linvel.normalize_or_zero();
+                damage_events.send(DamageEvent {
+                    target: other,
+                    amount: projectile.damage,
+                    source: DamageSource::Projectile,
+                    knockback: direction * PROJECTILE_KNOCKBACK,
+                    critical: false,
+                });
+            }
+
+            let position = transform.translation.truncate();
+            let radius = match &projectile.effect {
+                ProjectileEffect::None => None,
+                ProjectileEffect::Explode { radius, .. }
+                | ProjectileEffect::Place { radius, .. } => Some(*radius),
+            };
+            let Some(radius) = radius else {
+                if hit_enemy {
+                    commands.entity(entity).despawn_recursive();
+                } else {
+                    // stuck in the tile for a while
+                    projectile.lifetime = Timer::from_seconds(STUCK_LIFETIME, TimerMode::Once);
+                    commands
+                        .entity(entity)
+                        .insert(RigidBody::Fixed)
+                        .insert(Velocity::zero())
+                        .remove::<Collider>();
+                }
+                continue;
+            };
+            commands.entity(entity).despawn_recursive();
+
+            let Ok((tilemap, mut tile_storage, map_transform)) = tilemap_q.get_single_mut() else {
+                continue;
+            };
+            let map_offset = map_transform.translation.truncate();
+            let Some(center) = TilePos::from_world_pos(
+                &(position - map_offset),
+                &tile_storage.size,
+                &GRID_SIZE,
+                &MAP_TYPE,
+            ) else {
+                continue;
+            };
+            let cells = cells_around(center, radius, tile_storage.size);
+            match &projectile.effect {
+                ProjectileEffect::Explode { power, .. } => explode(
+                    &mut commands,
+                    &mut tile_storage,
+                    &cells,
+                    *power,
+                    &tile_q,
+                    &tile_types,
+                    &mut tile_changes,
+                    &mut destroyed_tiles,
+                ),
+                ProjectileEffect::Place { tile, .. } => {
+                    if let Some(tile_type) = tile_types.by_name(tile) {
+                        fill(
+                            &mut commands,
+                            tilemap,
+                            &mut tile_storage,
+                            &station_map,
+                            &cells,
+                            tile_type.get_texture_index(),
+                            map_offset,
+                            player_pos,
+                            &mut tile_changes,
+                        );
+                    }
+                }
+                ProjectileEffect::None => {}
+            }
+        }
+    }
+}
+
+pub fn expire_projectiles(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut projectile_q: Query<(Entity, &mut Projectile)>,
+) {
+    for (entity, mut projectile) in projectile_q.iter_mut() {
+        if projectile.lifetime.tick(time.delta()).finished() {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
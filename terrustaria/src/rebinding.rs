@@ -0,0 +1,330 @@
+use bevy::prelude::*;
+
+use crate::actions::{save_input_settings, Action, Binding, InputSettings};
+use crate::constants::{hud::*, menu::*};
+use crate::menu::{MenuButton, MenuRoot};
+
+// the binding waiting for the next pressed input, None as the index adds a new binding
+#[derive(Resource, Default)]
+pub struct Rebinding {
+    waiting: Option<(Action, Option<usize>)>,
+    // the click which started the rebinding must not become the new binding
+    armed: bool,
+}
+
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RebindButton {
+    Binding(Action, usize),
+    Add(Action),
+    ResetDefaults,
+}
+
+fn base_color(settings: &InputSettings, button: &RebindButton) -> Color {
+    match button {
+        RebindButton::Binding(action, idx) => {
+            let conflicting = settings
+                .bindings(*action)
+                .get(*idx)
+                .map_or(false, |binding| {
+                    !settings.conflicts(*action, binding).is_empty()
+                });
+            if conflicting {
+                CONFLICT_BUTTON_COLOR
+            } else {
+                BUTTON_COLOR
+            }
+        }
+        _ => BUTTON_COLOR,
+    }
+}
+
+fn persist(settings: &InputSettings) {
+    if let Err(err) = save_input_settings(settings) {
+        error!("cannot save the input settings: {err}");
+    }
+}
+
+// every binding shared by more than one action, listed once
+fn conflict_lines(settings: &InputSettings) -> Vec<String> {
+    let mut seen: Vec<Binding> = Vec::new();
+    let mut lines = Vec::new();
+    for action in Action::ALL {
+        for binding in settings.bindings(action) {
+            let others = settings.conflicts(action, binding);
+            if others.is_empty() || seen.contains(binding) {
+                continue;
+            }
+            seen.push(*binding);
+            let names: Vec<&str> = std::iter::once(action)
+                .chain(others)
+                .map(|action| action.name())
+                .collect();
+            lines.push(format!(
+                "{} is bound to {}",
+                binding.name(),
+                names.join(", ")
+            ));
+        }
+    }
+    lines
+}
+
+fn spawn_button<B: Component>(
+    parent: &mut ChildBuilder,
+    text_style: &TextStyle,
+    label: &str,
+    width: f32,
+    color: Color,
+    button: B,
+) {
+    parent
+        .spawn(ButtonBundle {
+            style: Style {
+                size: Size::new(Val::Px(width), Val::Auto),
+                margin: UiRect::all(Val::Px(MENU_GAP / 4.)),
+                padding: UiRect::all(Val::Px(PANEL_PADDING)),
+                justify_content: JustifyContent::Center,
+                ..default()
+            },
+            background_color: color.into(),
+            ..default()
+        })
+        .insert(button)
+        .with_children(|button_parent| {
+            button_parent.spawn(TextBundle::from_section(label, text_style.clone()));
+        });
+}
+
+pub fn reset_rebinding(mut rebinding: ResMut<Rebinding>) {
+    *rebinding = Rebinding::default();
+}
+
+// spawned again whenever a binding changes or starts waiting for an input
+pub fn update_rebinding_screen(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    settings: Res<InputSettings>,
+    rebinding: Res<Rebinding>,
+    menu_q: Query<Entity, With<MenuRoot>>,
+) {
+    if !settings.is_changed() && !rebinding.is_changed() {
+        return;
+    }
+    for menu in menu_q.iter() {
+        commands.entity(menu).despawn_recursive();
+    }
+
+    let font = asset_server.load(FONT);
+    let text_style = TextStyle {
+        font: font.clone(),
+        font_size: PANEL_FONT_SIZE,
+        color: Color::WHITE,
+    };
+    let waiting_label = "press an input...";
+
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.), Val::Percent(100.)),
+                flex_direction: FlexDirection::Column,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            background_color: OVERLAY_COLOR.into(),
+            ..default()
+        })
+        .insert(MenuRoot)
+        .insert(Name::new("Settings"))
+        .with_children(|parent| {
+            parent.spawn(
+                TextBundle::from_section(
+                    "Controls",
+                    TextStyle {
+                        font: font.clone(),
+                        font_size: TITLE_FONT_SIZE,
+                        color: Color::WHITE,
+                    },
+                )
+                .with_style(Style {
+                    margin: UiRect::bottom(Val::Px(MENU_GAP)),
+                    ..default()
+                }),
+            );
+            parent.spawn(TextBundle::from_section(
+                "Click a binding and press the new input, Backspace removes it, Escape cancels",
+                text_style.clone(),
+            ));
+
+            for action in Action::ALL {
+                parent
+                    .spawn(NodeBundle {
+                        style: Style {
+                            flex_direction: FlexDirection::Row,
+                            align_items: AlignItems::Center,
+                            ..default()
+                        },
+                        ..default()
+                    })
+                    .with_children(|row| {
+                        row.spawn(
+                            TextBundle::from_section(action.name(), text_style.clone()).with_style(
+                                Style {
+                                    size: Size::new(Val::Px(ACTION_LABEL_WIDTH), Val::Auto),
+                                    ..default()
+                                },
+                            ),
+                        );
+                        for (idx, binding) in settings.bindings(action).iter().enumerate() {
+                            let button = RebindButton::Binding(action, idx);
+                            let name = binding.name();
+                            let label = if rebinding.waiting == Some((action, Some(idx))) {
+                                waiting_label
+                            } else {
+                                name.as_str()
+                            };
+                            spawn_button(
+                                row,
+                                &text_style,
+                                label,
+                                BINDING_BUTTON_WIDTH,
+                                base_color(&settings, &button),
+                                button,
+                            );
+                        }
+                        let add_label = if rebinding.waiting == Some((action, None)) {
+                            waiting_label
+                        } else {
+                            "+"
+                        };
+                        spawn_button(
+                            row,
+                            &text_style,
+                            add_label,
+                            BINDING_BUTTON_WIDTH / 2.,
+                            BUTTON_COLOR,
+                            RebindButton::Add(action),
+                        );
+                    });
+            }
+
+            for line in conflict_lines(&settings) {
+                parent.spawn(TextBundle::from_section(
+                    line,
+                    TextStyle {
+                        color: MENU_ERROR_COLOR,
+                        ..text_style.clone()
+                    },
+                ));
+            }
+
+            spawn_button(
+                parent,
+                &text_style,
+                "Reset to defaults",
+                MENU_BUTTON_WIDTH,
+                BUTTON_COLOR,
+                RebindButton::ResetDefaults,
+            );
+            spawn_button(
+                parent,
+                &text_style,
+                "Back",
+                MENU_BUTTON_WIDTH,
+                BUTTON_COLOR,
+                MenuButton::Back,
+            );
+        });
+}
+
+pub fn handle_rebinding_buttons(
+    mut interaction_q: Query<
+        (&Interaction, &RebindButton, &mut BackgroundColor),
+        Changed<Interaction>,
+    >,
+    mut rebinding: ResMut<Rebinding>,
+    mut settings: ResMut<InputSettings>,
+) {
+    for (interaction, button, mut color) in interaction_q.iter_mut() {
+        match interaction {
+            Interaction::Hovered => *color = HOVERED_BUTTON_COLOR.into(),
+            Interaction::None => *color = base_color(&settings, button).into(),
+            // a click while waiting is the new binding, it is taken by capture_binding
+            Interaction::Clicked if rebinding.waiting.is_some() => {}
+            Interaction::Clicked => match button {
+                RebindButton::Binding(action, idx) => {
+                    *rebinding = Rebinding {
+                        waiting: Some((*action, Some(*idx))),
+                        armed: false,
+                    };
+                }
+                RebindButton::Add(action) => {
+                    *rebinding = Rebinding {
+                        waiting: Some((*action, None)),
+                        armed: false,
+                    };
+                }
+                RebindButton::ResetDefaults => {
+                    settings.bindings = InputSettings::default().bindings;
+                    persist(&settings);
+                }
+            },
+        }
+    }
+}
+
+// runs before the pause is toggled, so that escape only cancels the rebinding
+pub fn capture_binding(
+    mut keyboard_input: ResMut<Input<KeyCode>>,
+    mouse: Res<Input<MouseButton>>,
+    mut gamepad_buttons: ResMut<Input<GamepadButton>>,
+    mut rebinding: ResMut<Rebinding>,
+    mut settings: ResMut<InputSettings>,
+) {
+    let Some((action, index)) = rebinding.waiting else {
+        return;
+    };
+    if !rebinding.armed {
+        if mouse.get_pressed().next().is_none() {
+            rebinding.bypass_change_detection().armed = true;
+        }
+        return;
+    }
+
+    if keyboard_input.just_pressed(KeyCode::Escape) {
+        keyboard_input.reset(KeyCode::Escape);
+        rebinding.waiting = None;
+        return;
+    }
+    if keyboard_input.just_pressed(KeyCode::Back) {
+        if let Some(index) = index {
+            settings.unbind(action, index);
+            persist(&settings);
+        }
+        rebinding.waiting = None;
+        return;
+    }
+
+    let pressed = keyboard_input
+        .get_just_pressed()
+        .next()
+        .map(|key| Binding::Key(*key))
+        .or_else(|| {
+            mouse
+                .get_just_pressed()
+                .next()
+                .map(|button| Binding::Mouse(*button))
+        })
+        .or_else(|| {
+            let button = *gamepad_buttons.get_just_pressed().next()?;
+            // start would toggle the pause right after
+            gamepad_buttons.reset(button);
+            Some(Binding::Gamepad(button.button_type))
+        });
+    let Some(binding) = pressed else {
+        return;
+    };
+    settings.rebind(action, index, binding);
+    persist(&settings);
+    rebinding.waiting = None;
+}
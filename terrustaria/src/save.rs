@@ -0,0 +1,800 @@
+use std::collections::HashSet;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use bevy::app::AppExit;
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use bevy::window::WindowCloseRequested;
+use bevy_ecs_tilemap::prelude::*;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::actions::{Action, Actions, InputSettings};
+use crate::constants::{player::SPAWN_POSITION, save::*};
+use crate::death::SpawnPoint;
+use crate::drops::{spawn_item_drop, ItemDrop};
+use crate::game_state::AppState;
+use crate::health::Health;
+use crate::hotbar::Hotbar;
+use crate::inventory::Inventory;
+use crate::item::{ItemId, ItemKind, ItemRegistry, ItemSprites};
+use crate::map::{CoverMap, CoverTile, WallMap, WithColliders};
+use crate::menu::MenuButton;
+use crate::migrations::{migrate, Migration, PLAYER_MIGRATIONS, WORLD_MIGRATIONS};
+use crate::player::Player;
+use crate::save_chunks::{decode_world, ChunkCache, ChunkFile, ChunkSave};
+use crate::save_slots::{players_dir, read_meta, record_play_time, worlds_dir};
+use crate::save_slots::{CurrentSlots, PlayClock, PlayerMeta, WorldMeta};
+use crate::stations::{spawn_station, CraftingStation, StationMap, StationSprites};
+use crate::tile::TileCollection;
+use crate::world_settings::WorldSettings;
+
+// everything placed in the world, cells are stored row by row starting from the bottom one;
+// water is a foreground tile, so liquids are saved together with the blocks
+#[derive(Resource, Clone, Debug, PartialEq)]
+pub struct WorldSave {
+    pub width: u32,
+    pub height: u32,
+    // name of the foreground tile in every cell, None for air
+    pub tiles: Vec<Option<String>>,
+    pub walls: Vec<Option<String>>,
+    // cells the player has already uncovered
+    pub explored: Vec<bool>,
+    pub objects: Vec<ObjectSave>,
+    pub drops: Vec<DropSave>,
+}
+
+// header of the world as it is written to the disk, the layers are split into compressed
+// chunks which are written to files of their own
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct WorldFile {
+    pub version: u32,
+    pub width: u32,
+    pub height: u32,
+    // counts the saves of the world, the chunks changed by a save go to files of its generation
+    pub generation: u64,
+    pub chunk_files: Vec<ChunkFile>,
+    // chunks of a save migrated from before the chunk files, the next save writes them out
+    #[serde(default)]
+    pub chunks: Vec<ChunkSave>,
+    pub objects: Vec<ObjectSave>,
+    // worlds saved before the drops were kept have none laying around
+    #[serde(default)]
+    pub drops: Vec<DropSave>,
+}
+
+// crafting station standing in the world, its size and sprite come from the item
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ObjectSave {
+    pub item: ItemId,
+    pub x: u32,
+    pub y: u32,
+}
+
+// item laying on the ground, so that a death pile is still there after quitting;
+// the position is in the world and the lifetime is the part of it which is left
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct DropSave {
+    pub item: ItemId,
+    pub count: u32,
+    pub x: f32,
+    pub y: f32,
+    pub lifetime: f32,
+}
+
+#[derive(Resource, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PlayerSave {
+    pub version: u32,
+    pub position: (f32, f32),
+    pub inventory: Inventory,
+    pub hotbar_slot: usize,
+    // id of the world the position belongs to, saves from before the worlds had ids have none
+    #[serde(default)]
+    pub world: Option<String>,
+    // characters saved before they had health start with all of it
+    #[serde(default)]
+    pub health: Option<f32>,
+    // bottom left cell of the bed the character respawns at, in the world it was saved in
+    #[serde(default)]
+    pub bed: Option<(u32, u32)>,
+}
+
+// saves the game every time the timer finishes, a timer of no length never does
+#[derive(Resource)]
+pub struct Autosave {
+    timer: Timer,
+}
+
+#[derive(Debug)]
+pub enum SaveError {
+    Io(std::io::Error),
+    Serialize(ron::Error),
+    Deserialize(ron::error::SpannedError),
+    // the save was made for a map of another size
+    WrongSize,
+    // the save was made by a newer version of the game
+    UnsupportedVersion(u32),
+    // a chunk does not decode into the layers it should hold
+    Corrupted,
+    // there is no world or no character to save into
+    NoSlot,
+}
+
+// everything the save is captured from
+#[derive(SystemParam)]
+pub struct GameSnapshot<'w, 's> {
+    foreground_q: Query<'w, 's, &'static TileStorage, With<WithColliders>>,
+    wall_q: Query<'w, 's, &'static TileStorage, With<WallMap>>,
+    cover_q: Query<'w, 's, &'static TileStorage, With<CoverMap>>,
+    tile_q: Query<'w, 's, &'static TileTextureIndex>,
+    visible_q: Query<'w, 's, &'static TileVisible, With<CoverTile>>,
+    station_q: Query<'w, 's, &'static CraftingStation>,
+    drop_q: Query<'w, 's, (&'static Transform, &'static ItemDrop)>,
+    tile_types: Res<'w, TileCollection>,
+    chunk_cache: ResMut<'w, ChunkCache>,
+    play_clock: ResMut<'w, PlayClock>,
+    slots: Option<Res<'w, CurrentSlots>>,
+    player_q: Query<
+        'w,
+        's,
+        (
+            &'static Transform,
+            &'static Inventory,
+            &'static Hotbar,
+            &'static Health,
+            &'static SpawnPoint,
+        ),
+        With<Player>,
+    >,
+}
+
+impl fmt::Display for SaveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SaveError::Io(err) => write!(f, "{err}"),
+            SaveError::Serialize(err) => write!(f, "{err}"),
+            SaveError::Deserialize(err) => write!(f, "{err}"),
+            SaveError::WrongSize => write!(f, "the map size does not match"),
+            SaveError::UnsupportedVersion(version) => {
+                write!(f, "unsupported save version {version}")
+            }
+            SaveError::Corrupted => write!(f, "a chunk of the world is corrupted"),
+            SaveError::NoSlot => write!(f, "no world or character is selected"),
+        }
+    }
+}
+
+impl From<std::io::Error> for SaveError {
+    fn from(err: std::io::Error) -> Self {
+        SaveError::Io(err)
+    }
+}
+
+impl From<ron::Error> for SaveError {
+    fn from(err: ron::Error) -> Self {
+        SaveError::Serialize(err)
+    }
+}
+
+impl From<ron::error::SpannedError> for SaveError {
+    fn from(err: ron::error::SpannedError) -> Self {
+        SaveError::Deserialize(err)
+    }
+}
+
+impl Autosave {
+    pub fn new(interval: f32) -> Self {
+        Self {
+            timer: Timer::from_seconds(interval.max(0.), TimerMode::Repeating),
+        }
+    }
+}
+
+impl Default for Autosave {
+    fn default() -> Self {
+        Self::new(AUTOSAVE_INTERVAL)
+    }
+}
+
+impl WorldSave {
+    pub fn empty(width: u32, height: u32) -> Self {
+        let cells = (width * height) as usize;
+        Self {
+            width,
+            height,
+            tiles: vec![None; cells],
+            walls: vec![None; cells],
+            explored: vec![false; cells],
+            objects: Vec::new(),
+            drops: Vec::new(),
+        }
+    }
+
+    pub fn index(&self, pos: &TilePos) -> usize {
+        (pos.y * self.width + pos.x) as usize
+    }
+
+    pub fn tile(&self, pos: &TilePos) -> Option<&str> {
+        self.tiles[self.index(pos)].as_deref()
+    }
+
+    pub fn wall(&self, pos: &TilePos) -> Option<&str> {
+        self.walls[self.index(pos)].as_deref()
+    }
+
+    pub fn is_explored(&self, pos: &TilePos) -> bool {
+        self.explored[self.index(pos)]
+    }
+
+    // every list has to hold exactly one value per cell of the map
+    pub fn fits(&self, map_size: &TilemapSize) -> bool {
+        let cells = (map_size.x * map_size.y) as usize;
+        self.width == map_size.x
+            && self.height == map_size.y
+            && self.tiles.len() == cells
+            && self.walls.len() == cells
+            && self.explored.len() == cells
+    }
+}
+
+impl GameSnapshot<'_, '_> {
+    pub fn world(&self) -> WorldSave {
+        let foreground = self.foreground_q.single();
+        let walls = self.wall_q.single();
+        let cover = self.cover_q.single();
+        let map_size = foreground.size;
+        let mut world = WorldSave::empty(map_size.x, map_size.y);
+
+        for x in 0..map_size.x {
+            for y in 0..map_size.y {
+                let tile_pos = TilePos { x, y };
+                let idx = world.index(&tile_pos);
+                world.tiles[idx] = foreground
+                    .get(&tile_pos)
+                    .and_then(|entity| self.tile_q.get(entity).ok())
+                    .and_then(|texture| self.tile_types.by_texture_index(*texture))
+                    .map(|tile_type| String::from(tile_type.get_name()));
+                world.walls[idx] = walls
+                    .get(&tile_pos)
+                    .and_then(|entity| self.tile_q.get(entity).ok())
+                    .and_then(|texture| self.tile_types.wall_by_texture_index(*texture))
+                    .map(|wall_type| String::from(wall_type.get_name()));
+                // the building area has no cover tiles, it is always visible
+                world.explored[idx] = cover
+                    .get(&tile_pos)
+                    .and_then(|entity| self.visible_q.get(entity).ok())
+                    .map_or(true, |visible| !visible.0);
+            }
+        }
+
+        world.objects = self
+            .station_q
+            .iter()
+            .map(|station| ObjectSave {
+                item: station.item().clone(),
+                x: station.origin().x,
+                y: station.origin().y,
+            })
+            .collect();
+        world.drops = self
+            .drop_q
+            .iter()
+            .map(|(transform, drop)| DropSave {
+                item: drop.item().clone(),
+                count: drop.count(),
+                x: transform.translation.x,
+                y: transform.translation.y,
+                lifetime: drop.time_left(),
+            })
+            .collect();
+        world
+    }
+
+    // there is nothing to save in the menus
+    pub fn in_world(&self) -> bool {
+        !self.player_q.is_empty() && self.slots.is_some()
+    }
+
+    pub fn player(&self, world: &str) -> PlayerSave {
+        let (transform, inventory, hotbar, health, spawn_point) = self.player_q.single();
+        PlayerSave {
+            version: PLAYER_VERSION,
+            position: (transform.translation.x, transform.translation.y),
+            inventory: inventory.clone(),
+            hotbar_slot: hotbar.selected(),
+            world: Some(String::from(world)),
+            health: Some(health.current()),
+            bed: spawn_point.bed.map(|bed| (bed.x, bed.y)),
+        }
+    }
+}
+
+pub fn world_path(id: &str) -> PathBuf {
+    worlds_dir().join(id).join(WORLD_FILE)
+}
+
+fn chunks_dir(id: &str) -> PathBuf {
+    worlds_dir().join(id).join(CHUNKS_DIR)
+}
+
+pub fn chunk_path(id: &str, chunk: &ChunkFile) -> PathBuf {
+    let ChunkFile { x, y, generation } = chunk;
+    chunks_dir(id).join(format!("{x}_{y}_{generation}.ron"))
+}
+
+pub fn player_path(id: &str) -> PathBuf {
+    players_dir().join(id).join(PLAYER_FILE)
+}
+
+// the first generation is the newest one
+fn backup_path(path: &Path, generation: usize) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(format!(".bak{generation}"));
+    PathBuf::from(name)
+}
+
+// shifts the older backups by one generation and copies the current file into the first one,
+// the oldest backup gets overwritten
+fn rotate_backups(path: &Path) -> Result<(), SaveError> {
+    if !path.exists() {
+        return Ok(());
+    }
+    for generation in (1..BACKUP_COUNT).rev() {
+        let backup = backup_path(path, generation);
+        if backup.exists() {
+            fs::rename(&backup, backup_path(path, generation + 1))?;
+        }
+    }
+    fs::copy(path, backup_path(path, 1))?;
+    Ok(())
+}
+
+// the data goes to a temporary file first, which then replaces the old one in a single rename,
+// so a crash in the middle of writing leaves the previous save untouched
+fn write_file<T: Serialize>(path: &Path, value: &T) -> Result<(), SaveError> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let data = ron::ser::to_string_pretty(value, ron::ser::PrettyConfig::default())?;
+
+    let temp_path = path.with_extension("tmp");
+    let mut file = File::create(&temp_path)?;
+    file.write_all(data.as_bytes())?;
+    file.sync_all()?;
+
+    fs::rename(&temp_path, path)?;
+    Ok(())
+}
+
+pub fn write_ron<T: Serialize>(path: &Path, value: &T) -> Result<(), SaveError> {
+    rotate_backups(path)?;
+    write_file(path, value)
+}
+
+// older saves are upgraded to the current version before they are parsed
+pub fn read_ron<T: DeserializeOwned>(
+    path: &Path,
+    migrations: &[Migration],
+) -> Result<T, SaveError> {
+    let data = migrate(fs::read_to_string(path)?, migrations)?;
+    Ok(ron::from_str(&data)?)
+}
+
+pub fn save_game(snapshot: &mut GameSnapshot) -> Result<(), SaveError> {
+    let slots = snapshot
+        .slots
+        .as_deref()
+        .cloned()
+        .ok_or(SaveError::NoSlot)?;
+    let world = snapshot.world();
+    // only the chunks which changed are written, the small header is written every time;
+    // the chunks go to new files, so they need no backups of their own
+    let (world_file, chunks) = snapshot.chunk_cache.encode(&world)?;
+    for chunk in chunks.iter() {
+        let chunk_file = ChunkFile {
+            x: chunk.x,
+            y: chunk.y,
+            generation: world_file.generation,
+        };
+        write_file(&chunk_path(&slots.world, &chunk_file), chunk)?;
+    }
+    write_ron(&world_path(&slots.world), &world_file)?;
+    snapshot.chunk_cache.mark_written(&world_file);
+    if let Err(err) = prune_chunk_files(&slots.world) {
+        warn!("cannot remove the old chunks of the world: {err}");
+    }
+    write_ron(&player_path(&slots.player), &snapshot.player(&slots.world))?;
+
+    let played = snapshot.play_clock.take();
+    record_play_time::<WorldMeta>(&worlds_dir(), &slots.world, played)?;
+    record_play_time::<PlayerMeta>(&players_dir(), &slots.player, played)?;
+    Ok(())
+}
+
+// removes the chunk files which neither the header nor any of its backups points to
+fn prune_chunk_files(id: &str) -> Result<(), SaveError> {
+    let path = world_path(id);
+    let headers = std::iter::once(path.clone())
+        .chain((1..=BACKUP_COUNT).map(|generation| backup_path(&path, generation)));
+    let mut kept = HashSet::new();
+    for header in headers {
+        // a broken backup or one from before the chunk files points to nothing
+        let Ok(data) = fs::read_to_string(&header) else {
+            continue;
+        };
+        let Ok(file) = ron::from_str::<WorldFile>(&data) else {
+            continue;
+        };
+        kept.extend(file.chunk_files.iter().map(|chunk| chunk_path(id, chunk)));
+    }
+    for entry in fs::read_dir(chunks_dir(id))? {
+        let chunk = entry?.path();
+        if !kept.contains(&chunk) {
+            fs::remove_file(&chunk)?;
+        }
+    }
+    Ok(())
+}
+
+fn read_chunk(path: &Path) -> Result<ChunkSave, SaveError> {
+    Ok(ron::from_str(&fs::read_to_string(path)?)?)
+}
+
+// the chunks kept in the header of a migrated save are not on the disk yet;
+// a missing or broken chunk file makes the header fall back to an older backup,
+// whose chunks are still on the disk
+fn read_world(
+    path: &Path,
+    id: &str,
+    map_size: &TilemapSize,
+) -> Result<(WorldSave, ChunkCache), SaveError> {
+    let world_file: WorldFile = read_ron(path, &WORLD_MIGRATIONS)?;
+    if world_file.width != map_size.x || world_file.height != map_size.y {
+        return Err(SaveError::WrongSize);
+    }
+    let mut chunks = world_file.chunks.clone();
+    for chunk in world_file.chunk_files.iter() {
+        chunks.push(read_chunk(&chunk_path(id, chunk))?);
+    }
+    let world = decode_world(&world_file, &chunks)?;
+    if !world.fits(map_size) {
+        return Err(SaveError::WrongSize);
+    }
+    Ok((world, ChunkCache::loaded(&world_file)))
+}
+
+fn read_player(path: &Path) -> Result<PlayerSave, SaveError> {
+    read_ron(path, &PLAYER_MIGRATIONS)
+}
+
+// None when there is nothing saved yet;
+// a broken save is replaced with the newest backup which can still be read,
+// so that the next rotation does not push the broken file into the backups
+fn load_with_backups<T>(
+    path: &Path,
+    read: impl Fn(&Path) -> Result<T, SaveError>,
+) -> Result<Option<T>, SaveError> {
+    if !path.exists() {
+        return Ok(None);
+    }
+    let err = match read(path) {
+        Ok(loaded) => return Ok(Some(loaded)),
+        Err(err) => err,
+    };
+    error!("{} is broken: {err}", path.display());
+
+    for generation in 1..=BACKUP_COUNT {
+        let backup = backup_path(path, generation);
+        if !backup.exists() {
+            continue;
+        }
+        match read(&backup) {
+            Ok(loaded) => {
+                warn!("loaded backup {generation} instead");
+                if let Err(copy_err) = fs::copy(&backup, path) {
+                    warn!(
+                        "cannot restore {} from the backup: {copy_err}",
+                        path.display()
+                    );
+                }
+                return Ok(Some(loaded));
+            }
+            Err(backup_err) => warn!("backup {generation} is broken too: {backup_err}"),
+        }
+    }
+    Err(err)
+}
+
+// runs before the maps are spawned, so that they can be rebuilt from the save;
+// whatever was left from the previous world is dropped first
+pub fn load_saved_game(
+    mut commands: Commands,
+    slots: Res<CurrentSlots>,
+    input_settings: Res<InputSettings>,
+) {
+    commands.remove_resource::<WorldSave>();
+    commands.remove_resource::<PlayerSave>();
+    commands.insert_resource(ChunkCache::default());
+    commands.insert_resource(StationMap::default());
+    commands.insert_resource(Autosave::new(input_settings.autosave_interval));
+    commands.insert_resource(PlayClock::default());
+
+    let settings = match read_meta::<WorldMeta>(&worlds_dir(), &slots.world) {
+        Ok(meta) => meta.settings,
+        Err(err) => {
+            warn!("cannot read the settings of the world, using the default ones: {err}");
+            WorldSettings::default()
+        }
+    };
+    let map_size = settings.map_size();
+    commands.insert_resource(settings);
+
+    match load_with_backups(&world_path(&slots.world), |path| {
+        read_world(path, &slots.world, &map_size)
+    }) {
+        Ok(Some((world, chunks))) => {
+            info!("loaded the world {}", slots.world);
+            commands.insert_resource(world);
+            commands.insert_resource(chunks);
+        }
+        Ok(None) => info!("the world {} is new, generating it", slots.world),
+        Err(err) => warn!("cannot load the world, generating a new one: {err}"),
+    }
+
+    match load_with_backups(&player_path(&slots.player), read_player) {
+        Ok(Some(mut player)) => {
+            info!("loaded the character {}", slots.player);
+            // the position means nothing in another world
+            if player
+                .world
+                .as_ref()
+                .map_or(false, |world| *world != slots.world)
+            {
+                player.position = SPAWN_POSITION;
+                player.bed = None;
+            }
+            commands.insert_resource(player);
+        }
+        Ok(None) => info!("the character {} is new", slots.player),
+        Err(err) => warn!("cannot load the character, starting a new one: {err}"),
+    }
+}
+
+// runs the frame after the foreground map is spawned, the stations stand in front of it
+pub fn spawn_saved_stations(
+    mut commands: Commands,
+    saved: Option<Res<WorldSave>>,
+    registry: Res<ItemRegistry>,
+    station_sprites: Res<StationSprites>,
+    mut station_map: ResMut<StationMap>,
+    tilemap_q: Query<&TilemapSize, (With<WithColliders>, Added<TileStorage>)>,
+) {
+    let Some(saved) = saved else {
+        return;
+    };
+    let Ok(map_size) = tilemap_q.get_single() else {
+        return;
+    };
+
+    for object in saved.objects.iter() {
+        let Some(ItemKind::Station {
+            width,
+            height,
+            sprite,
+        }) = registry.get(&object.item).map(|def| &def.kind)
+        else {
+            warn!("unknown station {} in the save", object.item.0);
+            continue;
+        };
+        spawn_station(
+            &mut commands,
+            &station_sprites,
+            &mut station_map,
+            map_size,
+            object.item.clone(),
+            *sprite,
+            TilePos {
+                x: object.x,
+                y: object.y,
+            },
+            UVec2::new(*width, *height),
+        );
+    }
+}
+
+// runs the frame after the foreground map is spawned, like spawn_saved_stations
+pub fn spawn_saved_drops(
+    mut commands: Commands,
+    saved: Option<Res<WorldSave>>,
+    registry: Res<ItemRegistry>,
+    item_sprites: Res<ItemSprites>,
+    tilemap_q: Query<(), (With<WithColliders>, Added<TileStorage>)>,
+) {
+    let Some(saved) = saved else {
+        return;
+    };
+    if tilemap_q.is_empty() {
+        return;
+    }
+    for drop in saved.drops.iter() {
+        spawn_item_drop(
+            &mut commands,
+            &registry,
+            &item_sprites,
+            drop.item.clone(),
+            drop.count,
+            Vec2::new(drop.x, drop.y),
+            drop.lifetime,
+        );
+    }
+}
+
+pub fn save_and_report(snapshot: &mut GameSnapshot, reason: &str) {
+    match save_game(snapshot) {
+        Ok(()) => info!("game saved ({reason})"),
+        Err(err) => error!("cannot save the game ({reason}): {err}"),
+    }
+}
+
+pub fn save_game_on_key(actions: Actions, mut snapshot: GameSnapshot) {
+    if actions.just_pressed(Action::QuickSave) {
+        save_and_report(&mut snapshot, "quick save");
+    }
+}
+
+pub fn autosave_game(time: Res<Time>, mut autosave: ResMut<Autosave>, mut snapshot: GameSnapshot) {
+    if autosave.timer.duration().is_zero() {
+        return;
+    }
+    autosave.timer.tick(time.delta());
+    if autosave.timer.just_finished() {
+        save_and_report(&mut snapshot, "autosave");
+    }
+}
+
+// the game is closed with the quit button or with the button of the window,
+// the app keeps running until the end of the frame either way;
+// closing the window is followed by an exit a frame later, which must not save again
+pub fn save_game_on_exit(
+    mut exits: EventReader<AppExit>,
+    mut close_requests: EventReader<WindowCloseRequested>,
+    mut snapshot: GameSnapshot,
+    mut saved: Local<bool>,
+) {
+    let closing = exits.iter().count() + close_requests.iter().count() > 0;
+    if closing && !*saved && snapshot.in_world() {
+        save_and_report(&mut snapshot, "exit");
+        *saved = true;
+    }
+}
+
+// leaving the world despawns it, so it is saved right before
+pub fn save_and_quit_to_menu(
+    interaction_q: Query<(&Interaction, &MenuButton), Changed<Interaction>>,
+    mut state: ResMut<State<AppState>>,
+    mut snapshot: GameSnapshot,
+) {
+    let clicked = interaction_q.iter().any(|(interaction, button)| {
+        *interaction == Interaction::Clicked && *button == MenuButton::SaveAndQuit
+    });
+    if !clicked {
+        return;
+    }
+    save_and_report(&mut snapshot, "quit to menu");
+    if let Err(err) = state.set(AppState::MainMenu) {
+        warn!("cannot quit to the menu: {err:?}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::save_chunks::encode_world;
+
+    // wider than one chunk and higher than one, so that it gets split at both borders
+    fn sample_world() -> WorldSave {
+        let mut world = WorldSave::empty(40, 36);
+        for x in 0..40 {
+            for y in 0..36 {
+                let pos = TilePos { x, y };
+                let idx = world.index(&pos);
+                if y < 20 {
+                    let tile = if (x + y) % 3 == 0 { "Stone" } else { "Dirt" };
+                    world.tiles[idx] = Some(String::from(tile));
+                    world.walls[idx] = Some(String::from("DirtWall"));
+                }
+                world.explored[idx] = y >= 15 || x < 4;
+            }
+        }
+        let water = world.index(&TilePos { x: 5, y: 19 });
+        world.tiles[water] = Some(String::from("Water"));
+        world.objects = vec![
+            ObjectSave {
+                item: ItemId::new("workbench"),
+                x: 3,
+                y: 20,
+            },
+            ObjectSave {
+                item: ItemId::new("bed"),
+                x: 35,
+                y: 20,
+            },
+        ];
+        world.drops = vec![DropSave {
+            item: ItemId::new("stone_sword"),
+            count: 1,
+            x: -48.5,
+            y: 112.,
+            lifetime: 250.25,
+        }];
+        world
+    }
+
+    #[test]
+    fn world_round_trip() {
+        let world = sample_world();
+        let (header, chunks) = encode_world(&world).unwrap();
+
+        let header_text = ron::to_string(&header).unwrap();
+        let header: WorldFile =
+            ron::from_str(&migrate(header_text, &WORLD_MIGRATIONS).unwrap()).unwrap();
+        let chunks: Vec<ChunkSave> = chunks
+            .iter()
+            .map(|chunk| ron::from_str(&ron::to_string(chunk).unwrap()).unwrap())
+            .collect();
+
+        let loaded = decode_world(&header, &chunks).unwrap();
+        assert!(loaded.fits(&TilemapSize { x: 40, y: 36 }));
+        assert_eq!(loaded, world);
+    }
+
+    #[test]
+    fn broken_save_is_restored_from_backup() {
+        let dir = std::env::temp_dir().join(format!("terrustaria_backup_{}", std::process::id()));
+        let path = dir.join("player.ron");
+        let player = PlayerSave {
+            version: PLAYER_VERSION,
+            position: (1., 2.),
+            inventory: Inventory::default(),
+            hotbar_slot: 0,
+            world: None,
+            health: None,
+            bed: None,
+        };
+        write_ron(&path, &player).unwrap();
+        // the second write moves the first one into the backups
+        write_ron(&path, &player).unwrap();
+        fs::write(&path, "(broken").unwrap();
+
+        assert_eq!(
+            load_with_backups(&path, read_player).unwrap(),
+            Some(player.clone())
+        );
+        assert_eq!(read_player(&path).unwrap(), player);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn player_round_trip() {
+        let registry = ItemRegistry::default();
+        let mut inventory = Inventory::default();
+        inventory.add(&registry, &ItemId::new("dirt"), 150);
+        inventory.add(&registry, &ItemId::new("stone_sword"), 1);
+        let player = PlayerSave {
+            version: PLAYER_VERSION,
+            position: (-120.5, 48.),
+            inventory,
+            hotbar_slot: 3,
+            world: Some(String::from("world_2")),
+            health: Some(57.5),
+            bed: Some((12, 33)),
+        };
+
+        let text = ron::to_string(&player).unwrap();
+        let loaded: PlayerSave =
+            ron::from_str(&migrate(text, &PLAYER_MIGRATIONS).unwrap()).unwrap();
+        assert_eq!(loaded, player);
+    }
+}
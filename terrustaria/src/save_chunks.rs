@@ -0,0 +1,455 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::io::{Read, Write};
+
+use bevy::prelude::*;
+use bevy_ecs_tilemap::prelude::*;
+use flate2::{read::ZlibDecoder, write::ZlibEncoder, Compression};
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::constants::save::{SAVE_CHUNK_SIZE, WORLD_VERSION};
+use crate::map::{CoverTile, TileChanged};
+use crate::save::{SaveError, WorldFile, WorldSave};
+
+// run-length encoded and compressed layers of one chunk, written to the save as base64
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ChunkData(pub Vec<u8>);
+
+// every chunk is a file of its own, so it brings the names of its tiles and walls along;
+// a cell stores the index of its name in the palette plus one, zero is empty
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChunkSave {
+    pub x: u32,
+    pub y: u32,
+    pub palette: Vec<String>,
+    pub data: ChunkData,
+}
+
+// every save writes its chunks into new files named after its generation, so the backups
+// of the header keep pointing to the chunks they were saved with
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ChunkFile {
+    pub x: u32,
+    pub y: u32,
+    pub generation: u64,
+}
+
+// chunks already on the disk, only the dirty ones and the missing ones are written again
+#[derive(Resource, Default)]
+pub struct ChunkCache {
+    // generation of the last save
+    generation: u64,
+    // generation of the file every chunk on the disk is in
+    written: HashMap<UVec2, u64>,
+    dirty: HashSet<UVec2>,
+}
+
+struct ChunkDataVisitor;
+
+impl Serialize for ChunkData {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bytes(&self.0)
+    }
+}
+
+impl<'de> de::Visitor<'de> for ChunkDataVisitor {
+    type Value = ChunkData;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("bytes of a compressed chunk")
+    }
+
+    fn visit_bytes<E: de::Error>(self, bytes: &[u8]) -> Result<ChunkData, E> {
+        Ok(ChunkData(bytes.to_vec()))
+    }
+
+    fn visit_byte_buf<E: de::Error>(self, bytes: Vec<u8>) -> Result<ChunkData, E> {
+        Ok(ChunkData(bytes))
+    }
+}
+
+impl<'de> Deserialize<'de> for ChunkData {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_byte_buf(ChunkDataVisitor)
+    }
+}
+
+fn chunk_of(pos: &TilePos) -> UVec2 {
+    UVec2::new(pos.x, pos.y) / SAVE_CHUNK_SIZE
+}
+
+fn chunk_count(width: u32, height: u32) -> UVec2 {
+    UVec2::new(
+        (width + SAVE_CHUNK_SIZE - 1) / SAVE_CHUNK_SIZE,
+        (height + SAVE_CHUNK_SIZE - 1) / SAVE_CHUNK_SIZE,
+    )
+}
+
+// cells of the chunk row by row, the chunks at the edges of the map may be smaller
+fn chunk_cells(chunk: UVec2, width: u32, height: u32) -> Vec<TilePos> {
+    let min = chunk * SAVE_CHUNK_SIZE;
+    let max = (min + SAVE_CHUNK_SIZE).min(UVec2::new(width, height));
+    (min.y..max.y)
+        .flat_map(|y| (min.x..max.x).map(move |x| TilePos { x, y }))
+        .collect()
+}
+
+// pairs of a run length and a value, both little endian
+fn rle_encode(values: &[u16], out: &mut Vec<u8>) {
+    let mut idx = 0;
+    while idx < values.len() {
+        let value = values[idx];
+        let mut run: u16 = 1;
+        while idx + (run as usize) < values.len()
+            && values[idx + run as usize] == value
+            && run < u16::MAX
+        {
+            run += 1;
+        }
+        out.extend_from_slice(&run.to_le_bytes());
+        out.extend_from_slice(&value.to_le_bytes());
+        idx += run as usize;
+    }
+}
+
+fn read_u16(bytes: &mut &[u8]) -> Result<u16, SaveError> {
+    if bytes.len() < 2 {
+        return Err(SaveError::Corrupted);
+    }
+    let value = u16::from_le_bytes([bytes[0], bytes[1]]);
+    *bytes = &bytes[2..];
+    Ok(value)
+}
+
+// reads runs until exactly len values are decoded
+fn rle_decode(bytes: &mut &[u8], len: usize) -> Result<Vec<u16>, SaveError> {
+    let mut values = Vec::with_capacity(len);
+    while values.len() < len {
+        let run = read_u16(bytes)? as usize;
+        let value = read_u16(bytes)?;
+        if run == 0 || values.len() + run > len {
+            return Err(SaveError::Corrupted);
+        }
+        values.extend(std::iter::repeat(value).take(run));
+    }
+    Ok(values)
+}
+
+fn compress(data: &[u8]) -> Result<Vec<u8>, SaveError> {
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::best());
+    encoder.write_all(data)?;
+    Ok(encoder.finish()?)
+}
+
+fn decompress(data: &[u8]) -> Result<Vec<u8>, SaveError> {
+    let mut decoded = Vec::new();
+    ZlibDecoder::new(data).read_to_end(&mut decoded)?;
+    Ok(decoded)
+}
+
+fn palette_name(palette: &[String], value: u16) -> Result<Option<String>, SaveError> {
+    if value == 0 {
+        return Ok(None);
+    }
+    palette
+        .get(value as usize - 1)
+        .cloned()
+        .map(Some)
+        .ok_or(SaveError::Corrupted)
+}
+
+fn palette_id(palette: &mut Vec<String>, name: Option<&str>) -> u16 {
+    let Some(name) = name else {
+        return 0;
+    };
+    let idx = match palette.iter().position(|known| known == name) {
+        Some(idx) => idx,
+        None => {
+            palette.push(String::from(name));
+            palette.len() - 1
+        }
+    };
+    idx as u16 + 1
+}
+
+// the names missing from the palette are appended to it
+fn encode_layers(
+    world: &WorldSave,
+    chunk: UVec2,
+    palette: &mut Vec<String>,
+) -> Result<ChunkData, SaveError> {
+    let cells = chunk_cells(chunk, world.width, world.height);
+    let tiles: Vec<u16> = cells
+        .iter()
+        .map(|pos| palette_id(palette, world.tile(pos)))
+        .collect();
+    let walls: Vec<u16> = cells
+        .iter()
+        .map(|pos| palette_id(palette, world.wall(pos)))
+        .collect();
+    let explored: Vec<u16> = cells
+        .iter()
+        .map(|pos| world.is_explored(pos) as u16)
+        .collect();
+
+    let mut layers = Vec::new();
+    rle_encode(&tiles, &mut layers);
+    rle_encode(&walls, &mut layers);
+    rle_encode(&explored, &mut layers);
+    Ok(ChunkData(compress(&layers)?))
+}
+
+fn decode_layers(
+    world: &mut WorldSave,
+    chunk: UVec2,
+    palette: &[String],
+    data: &ChunkData,
+) -> Result<(), SaveError> {
+    let cells = chunk_cells(chunk, world.width, world.height);
+    let layers = decompress(&data.0)?;
+    let mut bytes = layers.as_slice();
+    let tiles = rle_decode(&mut bytes, cells.len())?;
+    let walls = rle_decode(&mut bytes, cells.len())?;
+    let explored = rle_decode(&mut bytes, cells.len())?;
+
+    for (i, pos) in cells.iter().enumerate() {
+        let idx = world.index(pos);
+        world.tiles[idx] = palette_name(palette, tiles[i])?;
+        world.walls[idx] = palette_name(palette, walls[i])?;
+        world.explored[idx] = explored[i] != 0;
+    }
+    Ok(())
+}
+
+pub fn encode_chunk(world: &WorldSave, chunk: UVec2) -> Result<ChunkSave, SaveError> {
+    let mut palette = Vec::new();
+    let data = encode_layers(world, chunk, &mut palette)?;
+    Ok(ChunkSave {
+        x: chunk.x,
+        y: chunk.y,
+        palette,
+        data,
+    })
+}
+
+// every chunk of the map
+pub fn chunks_of(width: u32, height: u32) -> Vec<UVec2> {
+    let count = chunk_count(width, height);
+    (0..count.y)
+        .flat_map(|y| (0..count.x).map(move |x| UVec2::new(x, y)))
+        .collect()
+}
+
+// the header of the world together with all of its chunks
+pub fn encode_world(world: &WorldSave) -> Result<(WorldFile, Vec<ChunkSave>), SaveError> {
+    let chunks = chunks_of(world.width, world.height)
+        .into_iter()
+        .map(|chunk| encode_chunk(world, chunk))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok((world_header(world), chunks))
+}
+
+fn world_header(world: &WorldSave) -> WorldFile {
+    WorldFile {
+        version: WORLD_VERSION,
+        width: world.width,
+        height: world.height,
+        generation: 0,
+        chunk_files: Vec::new(),
+        chunks: Vec::new(),
+        objects: world.objects.clone(),
+        drops: world.drops.clone(),
+    }
+}
+
+impl ChunkCache {
+    // the chunks of a loaded save which came from their own files are clean
+    // until something changes in them
+    pub fn loaded(file: &WorldFile) -> Self {
+        let mut cache = Self::default();
+        cache.mark_written(file);
+        cache
+    }
+
+    pub fn mark_dirty(&mut self, pos: &TilePos) {
+        self.dirty.insert(chunk_of(pos));
+    }
+
+    // the header and the chunks which changed since the last save or were never written,
+    // the header points to the files of the new chunks and of the unchanged ones
+    pub fn encode(&self, world: &WorldSave) -> Result<(WorldFile, Vec<ChunkSave>), SaveError> {
+        let mut header = world_header(world);
+        header.generation = self.generation + 1;
+        let mut chunks = Vec::new();
+        for chunk in chunks_of(world.width, world.height) {
+            let generation = match self.written.get(&chunk) {
+                Some(generation) if !self.dirty.contains(&chunk) => *generation,
+                _ => {
+                    chunks.push(encode_chunk(world, chunk)?);
+                    header.generation
+                }
+            };
+            header.chunk_files.push(ChunkFile {
+                x: chunk.x,
+                y: chunk.y,
+                generation,
+            });
+        }
+        Ok((header, chunks))
+    }
+
+    // called once the header is safely on the disk
+    pub fn mark_written(&mut self, file: &WorldFile) {
+        self.generation = file.generation;
+        for chunk in file.chunk_files.iter() {
+            self.written
+                .insert(UVec2::new(chunk.x, chunk.y), chunk.generation);
+        }
+        self.dirty.clear();
+    }
+}
+
+// chunks come from the header of a migrated save, or from their own files
+pub fn decode_world(file: &WorldFile, chunks: &[ChunkSave]) -> Result<WorldSave, SaveError> {
+    let mut world = WorldSave::empty(file.width, file.height);
+    let mut decoded = HashSet::new();
+    for chunk in chunks {
+        let pos = UVec2::new(chunk.x, chunk.y);
+        if !chunk_cells(pos, file.width, file.height).is_empty() {
+            decode_layers(&mut world, pos, &chunk.palette, &chunk.data)?;
+            decoded.insert(pos);
+        }
+    }
+    if chunks_of(file.width, file.height)
+        .iter()
+        .any(|chunk| !decoded.contains(chunk))
+    {
+        return Err(SaveError::Corrupted);
+    }
+    world.objects = file.objects.clone();
+    world.drops = file.drops.clone();
+    Ok(world)
+}
+// mined and placed tiles and newly uncovered cells make their chunks dirty
+pub fn mark_dirty_chunks(
+    mut tile_changes: EventReader<TileChanged>,
+    revealed_q: Query<
+        (&TilePos, ChangeTrackers<TileVisible>),
+        (With<CoverTile>, Changed<TileVisible>),
+    >,
+    mut cache: ResMut<ChunkCache>,
+) {
+    for TileChanged(tile_pos) in tile_changes.iter() {
+        cache.mark_dirty(tile_pos);
+    }
+    for (tile_pos, visible_tracker) in revealed_q.iter() {
+        // freshly spawned cover is already in the save it came from
+        if !visible_tracker.is_added() {
+            cache.mark_dirty(tile_pos);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::{map::BUILDING_HEIGHT, player::VISION_RADIUS};
+    use crate::map::{generate_world_grid, GridCell};
+    use crate::tile::TileCollection;
+    use crate::world_settings::WorldSettings;
+
+    // one value per cell of every layer took well over 100 KiB for the default world
+    const MAX_DEFAULT_WORLD_BYTES: usize = 32 * 1024;
+
+    // a new world of the default settings, laid out as the game captures it before a save
+    fn default_world() -> WorldSave {
+        let settings = WorldSettings::default();
+        let map_size = settings.map_size();
+        let grid = generate_world_grid(&mut settings.rng(), map_size);
+        let tile_types = TileCollection::new();
+        let ground = map_size.y - BUILDING_HEIGHT;
+        let middle = map_size.x / 2;
+
+        let mut world = WorldSave::empty(map_size.x, map_size.y);
+        for x in 0..map_size.x {
+            for y in 0..map_size.y {
+                let pos = TilePos { x, y };
+                let idx = world.index(&pos);
+                world.tiles[idx] = match grid.get(&pos) {
+                    GridCell::Tile(texture_index) => tile_types
+                        .by_texture_index(texture_index)
+                        .map(|tile_type| String::from(tile_type.get_name())),
+                    GridCell::Empty => None,
+                };
+                if y < ground {
+                    world.walls[idx] = Some(String::from(tile_types.dirt_wall().get_name()));
+                }
+                world.explored[idx] = y >= ground
+                    || (y >= ground - VISION_RADIUS && x.abs_diff(middle) < VISION_RADIUS);
+            }
+        }
+        world
+    }
+
+    fn chunk_bytes(header: &WorldFile, chunks: &[ChunkSave]) -> usize {
+        ron::to_string(header).unwrap().len()
+            + chunks
+                .iter()
+                .map(|chunk| ron::to_string(chunk).unwrap().len())
+                .sum::<usize>()
+    }
+
+    #[test]
+    fn default_world_is_small_and_loads_back() {
+        let world = default_world();
+        let (header, chunks) = encode_world(&world).unwrap();
+
+        let size = chunk_bytes(&header, &chunks);
+        assert!(
+            size < MAX_DEFAULT_WORLD_BYTES,
+            "the default world takes {size} bytes"
+        );
+
+        // through the text, as it goes through the files
+        let header: WorldFile = ron::from_str(&ron::to_string(&header).unwrap()).unwrap();
+        let chunks: Vec<ChunkSave> = chunks
+            .iter()
+            .map(|chunk| ron::from_str(&ron::to_string(chunk).unwrap()).unwrap())
+            .collect();
+        assert_eq!(decode_world(&header, &chunks).unwrap(), world);
+    }
+
+    #[test]
+    fn only_dirty_chunks_are_written_again() {
+        let world = default_world();
+        let mut cache = ChunkCache::default();
+
+        let (header, chunks) = cache.encode(&world).unwrap();
+        assert_eq!(chunks.len(), chunks_of(world.width, world.height).len());
+        cache.mark_written(&header);
+        assert!(cache.encode(&world).unwrap().1.is_empty());
+
+        cache.mark_dirty(&TilePos { x: 40, y: 3 });
+        let (header, chunks) = cache.encode(&world).unwrap();
+        let written: Vec<(u32, u32)> = chunks.iter().map(|chunk| (chunk.x, chunk.y)).collect();
+        assert_eq!(written, vec![(1, 0)]);
+
+        // the header of the second save keeps the other chunks in the files of the first one
+        assert_eq!(header.generation, 2);
+        for chunk in header.chunk_files.iter() {
+            let expected = if (chunk.x, chunk.y) == (1, 0) { 2 } else { 1 };
+            assert_eq!(chunk.generation, expected);
+        }
+    }
+
+    #[test]
+    fn missing_chunk_is_corrupted() {
+        let world = default_world();
+        let (header, mut chunks) = encode_world(&world).unwrap();
+        chunks.pop();
+        assert!(matches!(
+            decode_world(&header, &chunks),
+            Err(SaveError::Corrupted)
+        ));
+    }
+}
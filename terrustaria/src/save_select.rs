@@ -0,0 +1,769 @@
+use bevy::prelude::*;
+
+use crate::constants::{hud::*, menu::*};
+use crate::game_state::AppState;
+use crate::menu::MenuRoot;
+use crate::save_slots::*;
+use crate::world_settings::{Difficulty, WorldSettings, WorldSize};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SlotKind {
+    World,
+    Player,
+}
+
+// opened over the lists, only one at a time
+#[derive(Clone, Debug, PartialEq)]
+pub enum SelectForm {
+    NewWorld {
+        name: String,
+        // typed as text, so that it can be edited digit by digit
+        seed: String,
+        size: WorldSize,
+        difficulty: Difficulty,
+        seed_focused: bool,
+    },
+    NewPlayer {
+        name: String,
+    },
+    Rename {
+        kind: SlotKind,
+        id: String,
+        name: String,
+    },
+}
+
+// state of the selection screen, the screen is spawned again whenever it changes
+#[derive(Resource, Default)]
+pub struct SaveSelection {
+    worlds: Vec<SaveSlot<WorldMeta>>,
+    players: Vec<SaveSlot<PlayerMeta>>,
+    world: Option<String>,
+    player: Option<String>,
+    form: Option<SelectForm>,
+    // the delete button has to be clicked twice
+    confirm_delete: Option<SlotKind>,
+    // result of the last failed action
+    message: Option<String>,
+}
+
+#[derive(Component, Clone, Debug, PartialEq, Eq)]
+pub enum SelectButton {
+    Slot(SlotKind, String),
+    New(SlotKind),
+    Rename(SlotKind),
+    Duplicate(SlotKind),
+    Delete(SlotKind),
+    FocusName,
+    FocusSeed,
+    RandomSeed,
+    CycleSize,
+    CycleDifficulty,
+    Confirm,
+    Cancel,
+    Play,
+    Back,
+}
+
+fn root(kind: SlotKind) -> std::path::PathBuf {
+    match kind {
+        SlotKind::World => worlds_dir(),
+        SlotKind::Player => players_dir(),
+    }
+}
+
+fn format_play_time(seconds: f64) -> String {
+    let minutes = (seconds / 60.) as u64;
+    if minutes >= 60 {
+        format!("{} h {:02} min", minutes / 60, minutes % 60)
+    } else {
+        format!("{minutes} min")
+    }
+}
+
+fn format_last_played(last_played: u64) -> String {
+    if last_played == 0 {
+        return String::from("never played");
+    }
+    let ago = now().saturating_sub(last_played);
+    match ago {
+        0..=59 => String::from("just now"),
+        60..=3599 => format!("{} min ago", ago / 60),
+        3600..=86399 => format!("{} h ago", ago / 3600),
+        _ => format!("{} days ago", ago / 86400),
+    }
+}
+
+fn played_info<M: SlotMeta>(meta: &M) -> String {
+    format!(
+        "played {}, {}",
+        format_play_time(meta.play_time()),
+        format_last_played(meta.last_played())
+    )
+}
+
+impl SaveSelection {
+    // reads the lists from the disk again, the selection is kept when the slot still exists
+    fn refresh(&mut self) {
+        self.worlds = list_slots(&worlds_dir());
+        self.players = list_slots(&players_dir());
+        if !self
+            .worlds
+            .iter()
+            .any(|slot| Some(&slot.id) == self.world.as_ref())
+        {
+            self.world = self.worlds.first().map(|slot| slot.id.clone());
+        }
+        if !self
+            .players
+            .iter()
+            .any(|slot| Some(&slot.id) == self.player.as_ref())
+        {
+            self.player = self.players.first().map(|slot| slot.id.clone());
+        }
+    }
+
+    fn selected(&self, kind: SlotKind) -> Option<&String> {
+        match kind {
+            SlotKind::World => self.world.as_ref(),
+            SlotKind::Player => self.player.as_ref(),
+        }
+    }
+
+    fn select(&mut self, kind: SlotKind, id: String) {
+        match kind {
+            SlotKind::World => self.world = Some(id),
+            SlotKind::Player => self.player = Some(id),
+        }
+    }
+
+    fn selected_name(&self, kind: SlotKind) -> Option<String> {
+        let id = self.selected(kind)?;
+        match kind {
+            SlotKind::World => self
+                .worlds
+                .iter()
+                .find(|slot| &slot.id == id)
+                .map(|slot| slot.meta.name.clone()),
+            SlotKind::Player => self
+                .players
+                .iter()
+                .find(|slot| &slot.id == id)
+                .map(|slot| slot.meta.name.clone()),
+        }
+    }
+
+    // the text field receiving the typed characters
+    fn focused_text(&mut self) -> Option<(&mut String, bool)> {
+        match self.form.as_mut()? {
+            SelectForm::NewWorld {
+                seed,
+                seed_focused: true,
+                ..
+            } => Some((seed, true)),
+            SelectForm::NewWorld { name, .. }
+            | SelectForm::NewPlayer { name }
+            | SelectForm::Rename { name, .. } => Some((name, false)),
+        }
+    }
+
+    fn base_color(&self, button: &SelectButton) -> Color {
+        let highlighted = match (button, &self.form) {
+            (SelectButton::Slot(kind, id), _) => self.selected(*kind) == Some(id),
+            (SelectButton::FocusSeed, Some(SelectForm::NewWorld { seed_focused, .. })) => {
+                *seed_focused
+            }
+            (SelectButton::FocusName, Some(SelectForm::NewWorld { seed_focused, .. })) => {
+                !*seed_focused
+            }
+            (SelectButton::FocusName, Some(_)) => true,
+            (SelectButton::Delete(kind), _) => self.confirm_delete == Some(*kind),
+            _ => false,
+        };
+        if highlighted {
+            SELECTED_BUTTON_COLOR
+        } else {
+            BUTTON_COLOR
+        }
+    }
+
+    fn confirm_form(&mut self) -> Result<(), String> {
+        let Some(form) = self.form.clone() else {
+            return Ok(());
+        };
+        let mut name = match &form {
+            SelectForm::NewWorld { name, .. }
+            | SelectForm::NewPlayer { name }
+            | SelectForm::Rename { name, .. } => name.trim().to_string(),
+        };
+        // nothing can be typed with a gamepad, new slots get numbered names instead
+        if name.is_empty() {
+            name = match &form {
+                SelectForm::NewWorld { .. } => format!("World {}", self.worlds.len() + 1),
+                SelectForm::NewPlayer { .. } => format!("Character {}", self.players.len() + 1),
+                SelectForm::Rename { .. } => {
+                    return Err(String::from("The name cannot be empty"));
+                }
+            };
+        }
+
+        let result = match form {
+            SelectForm::NewWorld {
+                seed,
+                size,
+                difficulty,
+                ..
+            } => {
+                let seed = if seed.is_empty() {
+                    rand::random()
+                } else {
+                    seed.parse()
+                        .map_err(|_| String::from("The seed is too big"))?
+                };
+                let settings = WorldSettings {
+                    seed,
+                    size,
+                    difficulty,
+                    ..default()
+                };
+                create_slot(&worlds_dir(), &WorldMeta::new(name, settings))
+                    .map(|id| self.world = Some(id))
+            }
+            SelectForm::NewPlayer { .. } => {
+                create_slot(&players_dir(), &PlayerMeta::new(name)).map(|id| self.player = Some(id))
+            }
+            SelectForm::Rename { kind, id, .. } => match kind {
+                SlotKind::World => rename_slot::<WorldMeta>(&worlds_dir(), &id, name),
+                SlotKind::Player => rename_slot::<PlayerMeta>(&players_dir(), &id, name),
+            },
+        };
+        result.map_err(|err| err.to_string())?;
+        self.form = None;
+        self.refresh();
+        Ok(())
+    }
+
+    fn duplicate(&mut self, kind: SlotKind) -> Result<(), String> {
+        let Some(id) = self.selected(kind).cloned() else {
+            return Ok(());
+        };
+        let copy_id = match kind {
+            SlotKind::World => duplicate_slot::<WorldMeta>(&worlds_dir(), &id),
+            SlotKind::Player => duplicate_slot::<PlayerMeta>(&players_dir(), &id),
+        }
+        .map_err(|err| err.to_string())?;
+        self.select(kind, copy_id);
+        self.refresh();
+        Ok(())
+    }
+
+    fn delete(&mut self, kind: SlotKind) -> Result<(), String> {
+        let Some(id) = self.selected(kind).cloned() else {
+            return Ok(());
+        };
+        delete_slot(&root(kind), &id).map_err(|err| err.to_string())?;
+        self.refresh();
+        Ok(())
+    }
+
+    // returns the slots to play once both are chosen
+    fn click(&mut self, button: &SelectButton) -> Result<Option<CurrentSlots>, String> {
+        let confirming = self.confirm_delete.take();
+        match button {
+            SelectButton::Slot(kind, id) => self.select(*kind, id.clone()),
+            SelectButton::New(SlotKind::World) => {
+                self.form = Some(SelectForm::NewWorld {
+                    name: String::new(),
+                    seed: rand::random::<u64>().to_string(),
+                    size: WorldSize::default(),
+                    difficulty: Difficulty::default(),
+                    seed_focused: false,
+                });
+            }
+            SelectButton::New(SlotKind::Player) => {
+                self.form = Some(SelectForm::NewPlayer {
+                    name: String::new(),
+                });
+            }
+            SelectButton::Rename(kind) => {
+                if let (Some(id), Some(name)) =
+                    (self.selected(*kind).cloned(), self.selected_name(*kind))
+                {
+                    self.form = Some(SelectForm::Rename {
+                        kind: *kind,
+                        id,
+                        name,
+                    });
+                }
+            }
+            SelectButton::Duplicate(kind) => self.duplicate(*kind)?,
+            SelectButton::Delete(kind) => {
+                if confirming == Some(*kind) {
+                    self.delete(*kind)?;
+                } else if self.selected(*kind).is_some() {
+                    self.confirm_delete = Some(*kind);
+                }
+            }
+            SelectButton::FocusName | SelectButton::FocusSeed => {
+                if let Some(SelectForm::NewWorld { seed_focused, .. }) = self.form.as_mut() {
+                    *seed_focused = *button == SelectButton::FocusSeed;
+                }
+            }
+            SelectButton::RandomSeed => {
+                if let Some(SelectForm::NewWorld { seed, .. }) = self.form.as_mut() {
+                    *seed = rand::random::<u64>().to_string();
+                }
+            }
+            SelectButton::CycleSize => {
+                if let Some(SelectForm::NewWorld { size, .. }) = self.form.as_mut() {
+                    *size = size.next();
+                }
+            }
+            SelectButton::CycleDifficulty => {
+                if let Some(SelectForm::NewWorld { difficulty, .. }) = self.form.as_mut() {
+                    *difficulty = difficulty.next();
+                }
+            }
+            SelectButton::Confirm => self.confirm_form()?,
+            SelectButton::Cancel => self.form = None,
+            SelectButton::Play => {
+                let (Some(world), Some(player)) = (self.world.clone(), self.player.clone()) else {
+                    return Err(String::from("Choose or create a world and a character"));
+                };
+                return Ok(Some(CurrentSlots { world, player }));
+            }
+            // handled by the system, it needs to change the state
+            SelectButton::Back => {}
+        }
+        Ok(None)
+    }
+}
+
+fn spawn_button(
+    parent: &mut ChildBuilder,
+    text_style: &TextStyle,
+    label: &str,
+    width: f32,
+    color: Color,
+    button: SelectButton,
+) {
+    parent
+        .spawn(ButtonBundle {
+            style: Style {
+                size: Size::new(Val::Px(width), Val::Auto),
+                margin: UiRect::all(Val::Px(MENU_GAP / 2.)),
+                padding: UiRect::all(Val::Px(PANEL_PADDING)),
+                justify_content: JustifyContent::Center,
+                ..default()
+            },
+            background_color: color.into(),
+            ..default()
+        })
+        .insert(button)
+        .with_children(|button_parent| {
+            button_parent.spawn(TextBundle::from_section(label, text_style.clone()));
+        });
+}
+
+// a button showing the name of the slot with its metadata below
+fn spawn_slot_button(
+    parent: &mut ChildBuilder,
+    text_style: &TextStyle,
+    info_style: &TextStyle,
+    name: &str,
+    info: &[String],
+    color: Color,
+    button: SelectButton,
+) {
+    parent
+        .spawn(ButtonBundle {
+            style: Style {
+                size: Size::new(Val::Px(SLOT_BUTTON_WIDTH), Val::Auto),
+                margin: UiRect::all(Val::Px(MENU_GAP / 2.)),
+                padding: UiRect::all(Val::Px(PANEL_PADDING)),
+                flex_direction: FlexDirection::Column,
+                ..default()
+            },
+            background_color: color.into(),
+            ..default()
+        })
+        .insert(button)
+        .with_children(|button_parent| {
+            button_parent.spawn(TextBundle::from_section(name, text_style.clone()));
+            for line in info {
+                button_parent.spawn(TextBundle::from_section(line, info_style.clone()));
+            }
+        });
+}
+
+fn row(parent: &mut ChildBuilder, spawn_children: impl FnOnce(&mut ChildBuilder)) {
+    parent
+        .spawn(NodeBundle {
+            style: Style {
+                flex_direction: FlexDirection::Row,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::FlexStart,
+                ..default()
+            },
+            ..default()
+        })
+        .with_children(spawn_children);
+}
+
+fn column(parent: &mut ChildBuilder, spawn_children: impl FnOnce(&mut ChildBuilder)) {
+    parent
+        .spawn(NodeBundle {
+            style: Style {
+                flex_direction: FlexDirection::Column,
+                align_items: AlignItems::Center,
+                margin: UiRect::horizontal(Val::Px(MENU_GAP)),
+                ..default()
+            },
+            ..default()
+        })
+        .with_children(spawn_children);
+}
+
+fn spawn_slot_list(
+    parent: &mut ChildBuilder,
+    selection: &SaveSelection,
+    text_style: &TextStyle,
+    info_style: &TextStyle,
+    kind: SlotKind,
+) {
+    column(parent, |list| {
+        let title = match kind {
+            SlotKind::World => "Worlds",
+            SlotKind::Player => "Characters",
+        };
+        list.spawn(TextBundle::from_section(title, text_style.clone()));
+
+        let slots: Vec<(String, String, Vec<String>)> = match kind {
+            SlotKind::World => selection
+                .worlds
+                .iter()
+                .map(|slot| {
+                    let settings = &slot.meta.settings;
+                    let info = vec![
+                        format!(
+                            "{}, {}, seed {}",
+                            settings.size.name(),
+                            settings.difficulty.name(),
+                            settings.seed
+                        ),
+                        played_info(&slot.meta),
+                    ];
+                    (slot.id.clone(), slot.meta.name.clone(), info)
+                })
+                .collect(),
+            SlotKind::Player => selection
+                .players
+                .iter()
+                .map(|slot| {
+                    let info = vec![played_info(&slot.meta)];
+                    (slot.id.clone(), slot.meta.name.clone(), info)
+                })
+                .collect(),
+        };
+        if slots.is_empty() {
+            list.spawn(TextBundle::from_section(
+                "nothing here yet",
+                info_style.clone(),
+            ));
+        }
+        for (id, name, info) in slots {
+            let button = SelectButton::Slot(kind, id);
+            let color = selection.base_color(&button);
+            spawn_slot_button(list, text_style, info_style, &name, &info, color, button);
+        }
+
+        row(list, |actions| {
+            let delete_label = if selection.confirm_delete == Some(kind) {
+                "Sure?"
+            } else {
+                "Delete"
+            };
+            for (label, button) in [
+                ("New", SelectButton::New(kind)),
+                ("Rename", SelectButton::Rename(kind)),
+                ("Copy", SelectButton::Duplicate(kind)),
+                (delete_label, SelectButton::Delete(kind)),
+            ] {
+                let color = selection.base_color(&button);
+                spawn_button(
+                    actions,
+                    info_style,
+                    label,
+                    SMALL_BUTTON_WIDTH,
+                    color,
+                    button,
+                );
+            }
+        });
+    });
+}
+
+fn spawn_form(
+    parent: &mut ChildBuilder,
+    selection: &SaveSelection,
+    form: &SelectForm,
+    text_style: &TextStyle,
+) {
+    let field = |text: &str, focused: bool| {
+        if focused {
+            format!("{text}_")
+        } else {
+            String::from(text)
+        }
+    };
+    let button = |parent: &mut ChildBuilder, label: &str, button: SelectButton| {
+        let color = selection.base_color(&button);
+        spawn_button(parent, text_style, label, MENU_BUTTON_WIDTH, color, button);
+    };
+
+    column(parent, |panel| match form {
+        SelectForm::NewWorld {
+            name,
+            seed,
+            size,
+            difficulty,
+            seed_focused,
+        } => {
+            panel.spawn(TextBundle::from_section("New world", text_style.clone()));
+            button(
+                panel,
+                &format!("Name: {}", field(name, !seed_focused)),
+                SelectButton::FocusName,
+            );
+            button(
+                panel,
+                &format!("Seed: {}", field(seed, *seed_focused)),
+                SelectButton::FocusSeed,
+            );
+            button(panel, "Random seed", SelectButton::RandomSeed);
+            button(
+                panel,
+                &format!("Size: {}", size.name()),
+                SelectButton::CycleSize,
+            );
+            button(
+                panel,
+                &format!("Difficulty: {}", difficulty.name()),
+                SelectButton::CycleDifficulty,
+            );
+            button(panel, "Create", SelectButton::Confirm);
+            button(panel, "Cancel", SelectButton::Cancel);
+        }
+        SelectForm::NewPlayer { name } => {
+            panel.spawn(TextBundle::from_section(
+                "New character",
+                text_style.clone(),
+            ));
+            button(
+                panel,
+                &format!("Name: {}", field(name, true)),
+                SelectButton::FocusName,
+            );
+            button(panel, "Create", SelectButton::Confirm);
+            button(panel, "Cancel", SelectButton::Cancel);
+        }
+        SelectForm::Rename { name, .. } => {
+            panel.spawn(TextBundle::from_section("Rename", text_style.clone()));
+            button(
+                panel,
+                &format!("Name: {}", field(name, true)),
+                SelectButton::FocusName,
+            );
+            button(panel, "Save", SelectButton::Confirm);
+            button(panel, "Cancel", SelectButton::Cancel);
+        }
+    });
+}
+
+// saves from before this screen existed are moved into their own directories first
+pub fn refresh_save_selection(mut selection: ResMut<SaveSelection>) {
+    if let Err(err) = import_legacy_save() {
+        error!("cannot move the old save: {err}");
+    }
+    selection.form = None;
+    selection.confirm_delete = None;
+    selection.message = None;
+    selection.refresh();
+}
+
+pub fn update_save_select(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    selection: Res<SaveSelection>,
+    menu_q: Query<Entity, With<MenuRoot>>,
+) {
+    if !selection.is_changed() {
+        return;
+    }
+    for menu in menu_q.iter() {
+        commands.entity(menu).despawn_recursive();
+    }
+
+    let font = asset_server.load(FONT);
+    let text_style = TextStyle {
+        font: font.clone(),
+        font_size: MENU_FONT_SIZE,
+        color: Color::WHITE,
+    };
+    let info_style = TextStyle {
+        font: font.clone(),
+        font_size: SLOT_INFO_FONT_SIZE,
+        color: Color::WHITE,
+    };
+
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.), Val::Percent(100.)),
+                flex_direction: FlexDirection::Column,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            background_color: MENU_BACKGROUND.into(),
+            ..default()
+        })
+        .insert(MenuRoot)
+        .insert(Name::new("SaveSelect"))
+        .with_children(|parent| {
+            parent.spawn(
+                TextBundle::from_section(
+                    "Select a world and a character",
+                    TextStyle {
+                        font,
+                        font_size: TITLE_FONT_SIZE,
+                        color: Color::WHITE,
+                    },
+                )
+                .with_style(Style {
+                    margin: UiRect::bottom(Val::Px(MENU_GAP * 2.)),
+                    ..default()
+                }),
+            );
+            if let Some(message) = &selection.message {
+                parent.spawn(TextBundle::from_section(
+                    message.as_str(),
+                    TextStyle {
+                        color: MENU_ERROR_COLOR,
+                        ..info_style.clone()
+                    },
+                ));
+            }
+
+            if let Some(form) = &selection.form {
+                spawn_form(parent, &selection, form, &text_style);
+                return;
+            }
+
+            row(parent, |lists| {
+                spawn_slot_list(lists, &selection, &text_style, &info_style, SlotKind::World);
+                spawn_slot_list(
+                    lists,
+                    &selection,
+                    &text_style,
+                    &info_style,
+                    SlotKind::Player,
+                );
+            });
+            row(parent, |buttons| {
+                spawn_button(
+                    buttons,
+                    &text_style,
+                    "Play",
+                    MENU_BUTTON_WIDTH,
+                    BUTTON_COLOR,
+                    SelectButton::Play,
+                );
+                spawn_button(
+                    buttons,
+                    &text_style,
+                    "Back",
+                    MENU_BUTTON_WIDTH,
+                    BUTTON_COLOR,
+                    SelectButton::Back,
+                );
+            });
+        });
+}
+
+pub fn handle_select_buttons(
+    mut commands: Commands,
+    mut interaction_q: Query<
+        (&Interaction, &SelectButton, &mut BackgroundColor),
+        Changed<Interaction>,
+    >,
+    mut selection: ResMut<SaveSelection>,
+    mut state: ResMut<State<AppState>>,
+) {
+    for (interaction, button, mut color) in interaction_q.iter_mut() {
+        match interaction {
+            Interaction::Hovered => *color = HOVERED_BUTTON_COLOR.into(),
+            Interaction::None => *color = selection.base_color(button).into(),
+            Interaction::Clicked => {
+                if *button == SelectButton::Back {
+                    if let Err(err) = state.set(AppState::MainMenu) {
+                        warn!("cannot leave the selection: {err:?}");
+                    }
+                    return;
+                }
+                match selection.click(button) {
+                    Ok(Some(slots)) => {
+                        commands.insert_resource(slots);
+                        if let Err(err) = state.set(AppState::Loading) {
+                            warn!("cannot start the game: {err:?}");
+                        }
+                    }
+                    Ok(None) => selection.message = None,
+                    Err(message) => selection.message = Some(message),
+                }
+                // the screen is about to be spawned again
+                return;
+            }
+        }
+    }
+}
+
+// typed characters go to the focused field of the open form, enter confirms it
+pub fn type_in_select_form(
+    mut typed: EventReader<ReceivedCharacter>,
+    keyboard_input: Res<Input<KeyCode>>,
+    mut selection: ResMut<SaveSelection>,
+) {
+    let chars: Vec<char> = typed.iter().map(|event| event.char).collect();
+    if selection.form.is_none() {
+        return;
+    }
+
+    if !chars.is_empty() {
+        if let Some((text, digits_only)) = selection.focused_text() {
+            for c in chars {
+                match c {
+                    // backspace and delete
+                    '\u{8}' | '\u{7f}' => {
+                        text.pop();
+                    }
+                    c if c.is_control() => {}
+                    c if digits_only => {
+                        if c.is_ascii_digit() && text.len() < SEED_MAX_LENGTH {
+                            text.push(c);
+                        }
+                    }
+                    c => {
+                        if text.chars().count() < NAME_MAX_LENGTH {
+                            text.push(c);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    if keyboard_input.just_pressed(KeyCode::Return) {
+        selection.message = selection.confirm_form().err();
+    }
+}
@@ -0,0 +1,299 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use bevy::prelude::*;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::constants::save::*;
+use crate::save::{write_ron, SaveError};
+use crate::world_settings::WorldSettings;
+
+// read from META_FILE in the directory of a world, the list of worlds is built from these only
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct WorldMeta {
+    pub name: String,
+    pub settings: WorldSettings,
+    // seconds since the unix epoch, zero until the world is played for the first time
+    pub last_played: u64,
+    // seconds spent in the world
+    pub play_time: f64,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PlayerMeta {
+    pub name: String,
+    pub last_played: u64,
+    pub play_time: f64,
+}
+
+// a world or a character, the id is the name of its directory and never changes
+#[derive(Clone, Debug, PartialEq)]
+pub struct SaveSlot<M> {
+    pub id: String,
+    pub meta: M,
+}
+
+// the world and the character being played, everything is saved into their directories
+#[derive(Resource, Clone, Debug, PartialEq, Eq)]
+pub struct CurrentSlots {
+    pub world: String,
+    pub player: String,
+}
+
+// time played since the last save, it is added to both metas when the game gets saved
+#[derive(Resource, Default)]
+pub struct PlayClock {
+    unsaved: f64,
+}
+
+pub trait SlotMeta: Clone + Serialize + DeserializeOwned {
+    fn name(&self) -> &str;
+    fn set_name(&mut self, name: String);
+    fn last_played(&self) -> u64;
+    fn play_time(&self) -> f64;
+    fn add_play_time(&mut self, seconds: f64);
+}
+
+impl SlotMeta for WorldMeta {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn set_name(&mut self, name: String) {
+        self.name = name;
+    }
+
+    fn last_played(&self) -> u64 {
+        self.last_played
+    }
+
+    fn play_time(&self) -> f64 {
+        self.play_time
+    }
+
+    fn add_play_time(&mut self, seconds: f64) {
+        self.play_time += seconds;
+        self.last_played = now();
+    }
+}
+
+impl SlotMeta for PlayerMeta {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn set_name(&mut self, name: String) {
+        self.name = name;
+    }
+
+    fn last_played(&self) -> u64 {
+        self.last_played
+    }
+
+    fn play_time(&self) -> f64 {
+        self.play_time
+    }
+
+    fn add_play_time(&mut self, seconds: f64) {
+        self.play_time += seconds;
+        self.last_played = now();
+    }
+}
+
+impl WorldMeta {
+    pub fn new(name: String, settings: WorldSettings) -> Self {
+        Self {
+            name,
+            settings,
+            last_played: 0,
+            play_time: 0.,
+        }
+    }
+}
+
+impl PlayerMeta {
+    pub fn new(name: String) -> Self {
+        Self {
+            name,
+            last_played: 0,
+            play_time: 0.,
+        }
+    }
+}
+
+impl PlayClock {
+    // hands over the time played so far and starts counting from zero again
+    pub fn take(&mut self) -> f64 {
+        std::mem::take(&mut self.unsaved)
+    }
+}
+
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs())
+}
+
+pub fn worlds_dir() -> PathBuf {
+    Path::new(SAVE_DIR).join(WORLDS_DIR)
+}
+
+pub fn players_dir() -> PathBuf {
+    Path::new(SAVE_DIR).join(PLAYERS_DIR)
+}
+
+fn meta_path(root: &Path, id: &str) -> PathBuf {
+    root.join(id).join(META_FILE)
+}
+
+pub fn read_meta<M: SlotMeta>(root: &Path, id: &str) -> Result<M, SaveError> {
+    Ok(ron::from_str(&fs::read_to_string(meta_path(root, id))?)?)
+}
+
+fn write_meta<M: SlotMeta>(root: &Path, id: &str, meta: &M) -> Result<(), SaveError> {
+    write_ron(&meta_path(root, id), meta)
+}
+
+// the most recently played slots come first, directories without a readable meta are skipped
+pub fn list_slots<M: SlotMeta>(root: &Path) -> Vec<SaveSlot<M>> {
+    let Ok(entries) = fs::read_dir(root) else {
+        return Vec::new();
+    };
+    let mut slots: Vec<SaveSlot<M>> = entries
+        .flatten()
+        .filter(|entry| entry.path().is_dir())
+        .filter_map(|entry| {
+            let id = entry.file_name().to_string_lossy().into_owned();
+            match read_meta(root, &id) {
+                Ok(meta) => Some(SaveSlot { id, meta }),
+                Err(err) => {
+                    warn!("skipping the save {id}: {err}");
+                    None
+                }
+            }
+        })
+        .collect();
+    slots.sort_by(|a, b| {
+        b.meta
+            .last_played()
+            .cmp(&a.meta.last_played())
+            .then_with(|| a.meta.name().cmp(b.meta.name()))
+    });
+    slots
+}
+
+// directory name made of the name of the slot, numbered when it is already taken
+fn unique_id(root: &Path, name: &str) -> String {
+    let mut base: String = name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_lowercase()
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    if base.trim_matches('_').is_empty() {
+        base = String::from("save");
+    }
+    let mut id = base.clone();
+    let mut number = 2;
+    while root.join(&id).exists() {
+        id = format!("{base}_{number}");
+        number += 1;
+    }
+    id
+}
+
+// the save itself is written the first time the slot is played
+pub fn create_slot<M: SlotMeta>(root: &Path, meta: &M) -> Result<String, SaveError> {
+    let id = unique_id(root, meta.name());
+    write_meta(root, &id, meta)?;
+    Ok(id)
+}
+
+pub fn rename_slot<M: SlotMeta>(root: &Path, id: &str, name: String) -> Result<(), SaveError> {
+    let mut meta: M = read_meta(root, id)?;
+    meta.set_name(name);
+    write_meta(root, id, &meta)
+}
+
+pub fn delete_slot(root: &Path, id: &str) -> Result<(), SaveError> {
+    fs::remove_dir_all(root.join(id))?;
+    Ok(())
+}
+
+fn copy_dir(from: &Path, to: &Path) -> Result<(), SaveError> {
+    fs::create_dir_all(to)?;
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        let target = to.join(entry.file_name());
+        if entry.path().is_dir() {
+            copy_dir(&entry.path(), &target)?;
+        } else {
+            fs::copy(entry.path(), target)?;
+        }
+    }
+    Ok(())
+}
+
+// the copy keeps the save and the play time, only its name tells it apart
+pub fn duplicate_slot<M: SlotMeta>(root: &Path, id: &str) -> Result<String, SaveError> {
+    let mut meta: M = read_meta(root, id)?;
+    meta.set_name(format!("{} (copy)", meta.name()));
+    let copy_id = unique_id(root, meta.name());
+    copy_dir(&root.join(id), &root.join(&copy_id))?;
+    write_meta(root, &copy_id, &meta)?;
+    Ok(copy_id)
+}
+
+pub fn record_play_time<M: SlotMeta>(root: &Path, id: &str, seconds: f64) -> Result<(), SaveError> {
+    let mut meta: M = read_meta(root, id)?;
+    meta.add_play_time(seconds);
+    write_meta(root, id, &meta)
+}
+
+fn move_with_backups(from: &Path, to: &Path) -> Result<(), SaveError> {
+    let Some(file_name) = from.file_name() else {
+        return Ok(());
+    };
+    for entry in fs::read_dir(from.parent().unwrap_or(Path::new(".")))? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if let Some(suffix) = name.strip_prefix(&*file_name.to_string_lossy()) {
+            let mut target = to.as_os_str().to_owned();
+            target.push(suffix);
+            fs::rename(entry.path(), target)?;
+        }
+    }
+    Ok(())
+}
+
+// saves from before the selection screen lay directly in SAVE_DIR,
+// they become a world and a character of their own; the old map had the medium size
+pub fn import_legacy_save() -> Result<(), SaveError> {
+    let world_file = Path::new(SAVE_DIR).join(WORLD_FILE);
+    if world_file.exists() {
+        let id = create_slot(
+            &worlds_dir(),
+            &WorldMeta::new(String::from("World"), WorldSettings::default()),
+        )?;
+        move_with_backups(&world_file, &worlds_dir().join(id).join(WORLD_FILE))?;
+        info!("moved the old world into its own directory");
+    }
+
+    let player_file = Path::new(SAVE_DIR).join(PLAYER_FILE);
+    if player_file.exists() {
+        let id = create_slot(&players_dir(), &PlayerMeta::new(String::from("Player")))?;
+        move_with_backups(&player_file, &players_dir().join(id).join(PLAYER_FILE))?;
+        info!("moved the old character into its own directory");
+    }
+    Ok(())
+}
+
+// the menus do not count as played time
+pub fn tick_play_clock(time: Res<Time>, mut clock: ResMut<PlayClock>) {
+    clock.unsaved += time.delta_seconds_f64();
+}
@@ -0,0 +1,452 @@
+use bevy::prelude::*;
+use bevy_ecs_tilemap::prelude::*;
+use rand::prelude::*;
+use serde::Deserialize;
+
+use crate::clock::{TimeOfDay, WorldClock};
+use crate::constants::map::{
+    BUILDING_HEIGHT, CAVERNS_DEPTH, GRID_SIZE, MAP_TYPE, UNDERGROUND_DEPTH,
+};
+use crate::enemy::EnemyKind;
+use crate::health::Health;
+use crate::map::{GridCell, WithColliders, WorldGrid};
+use crate::player::Player;
+use crate::tile::TileCollection;
+use crate::world_settings::WorldSettings;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+pub enum DepthLayer {
+    Surface,
+    Underground,
+    Caverns,
+}
+
+impl DepthLayer {
+    // counted from the top of the generated ground, everything above it is the surface
+    pub fn of(y: u32, map_size: TilemapSize) -> Self {
+        let depth = (map_size.y - BUILDING_HEIGHT).saturating_sub(y);
+        if depth >= CAVERNS_DEPTH {
+            DepthLayer::Caverns
+        } else if depth >= UNDERGROUND_DEPTH {
+            DepthLayer::Underground
+        } else {
+            DepthLayer::Surface
+        }
+    }
+}
+
+// only the sky lights the world for now, cells below a solid one stay dark
+pub fn light_level(grid: &WorldGrid, tile_pos: &TilePos, clock: &WorldClock) -> f32 {
+    if tile_pos.y >= grid.sky_row(tile_pos.x) {
+        clock.daylight()
+    } else {
+        0.
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize)]
+pub struct LightRange {
+    #[serde(default)]
+    pub min: f32,
+    #[serde(default = "full_light")]
+    pub max: f32,
+}
+
+fn full_light() -> f32 {
+    1.
+}
+
+fn normal_strength() -> f32 {
+    1.
+}
+
+impl Default for LightRange {
+    fn default() -> Self {
+        Self {
+            min: 0.,
+            max: full_light(),
+        }
+    }
+}
+
+// an enemy which may appear where all of the conditions hold, an empty list allows everything
+#[derive(Clone, Debug, Deserialize)]
+pub struct SpawnRule {
+    pub enemy: EnemyKind,
+    // chance against the other rules allowed at the same spot
+    pub weight: u32,
+    // multiplied with the enemy strength of the world difficulty
+    #[serde(default = "normal_strength")]
+    pub strength: f32,
+    #[serde(default)]
+    pub layers: Vec<DepthLayer>,
+    // until worlds get real biomes, the biome of a spot is the name of the tile under it
+    #[serde(default)]
+    pub biomes: Vec<String>,
+    #[serde(default)]
+    pub light: LightRange,
+    #[serde(default)]
+    pub time: Option<TimeOfDay>,
+}
+
+// everything the rules look at in a cell an enemy could appear in
+#[derive(Clone, Debug, PartialEq)]
+pub struct SpawnSpot {
+    pub cell: TilePos,
+    pub layer: DepthLayer,
+    pub biome: String,
+    pub light: f32,
+    pub time: TimeOfDay,
+}
+
+impl SpawnRule {
+    pub fn allows(&self, spot: &SpawnSpot) -> bool {
+        (self.layers.is_empty() || self.layers.contains(&spot.layer))
+            && (self.biomes.is_empty() || self.biomes.contains(&spot.biome))
+            && (self.light.min..=self.light.max).contains(&spot.light)
+            && self.time.map_or(true, |time| time == spot.time)
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct SpawnRules {
+    // seconds between two spawn attempts
+    pub interval: f32,
+    // spots tried in one attempt before giving up
+    pub tries: u32,
+    // tiles from the player in each direction which are still on the screen
+    pub visible: (u32, u32),
+    // tiles from the player in each direction a spot can be at most
+    pub reach: u32,
+    // the world is split into squares of this many tiles, each holding a few enemies at most
+    pub area_size: u32,
+    pub area_cap: usize,
+    pub enemies: Vec<SpawnRule>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Spawn {
+    pub enemy: EnemyKind,
+    pub cell: TilePos,
+    pub strength: f32,
+}
+
+impl SpawnRules {
+    pub fn from_ron(data: &str) -> Result<Self, ron::error::SpannedError> {
+        ron::from_str(data)
+    }
+
+    fn area(&self, tile_pos: &TilePos) -> UVec2 {
+        UVec2::new(tile_pos.x, tile_pos.y) / self.area_size.max(1)
+    }
+
+    // an empty cell on top of a solid one, off the screen but in reach of the player
+    fn pick_spot(
+        &self,
+        grid: &WorldGrid,
+        tile_types: &TileCollection,
+        clock: &WorldClock,
+        player: TilePos,
+        rng: &mut impl Rng,
+    ) -> Option<SpawnSpot> {
+        let size = grid.size();
+        let reach = self.reach as i64;
+        let dx = rng.gen_range(-reach..=reach);
+        let x = player.x as i64 + dx;
+        if x < 0 || x >= size.x as i64 {
+            return None;
+        }
+        let x = x as u32;
+        let low = player.y.saturating_sub(self.reach).max(1);
+        let high = (player.y + self.reach).min(size.y - 1);
+        let cells: Vec<TilePos> = (low..=high)
+            .map(|y| TilePos { x, y })
+            .filter(|cell| {
+                let hidden = dx.unsigned_abs() > self.visible.0 as u64
+                    || cell.y.abs_diff(player.y) > self.visible.1;
+                hidden && !grid.is_solid(cell) && grid.is_solid(&TilePos { x, y: cell.y - 1 })
+            })
+            .collect();
+        let cell = *cells.choose(rng)?;
+
+        let biome = match grid.get(&TilePos { x, y: cell.y - 1 }) {
+            GridCell::Tile(texture_index) => tile_types
+                .by_texture_index(texture_index)
+                .map_or("", |tile_type| tile_type.get_name()),
+            GridCell::Empty => "",
+        };
+        Some(SpawnSpot {
+            cell,
+            layer: DepthLayer::of(cell.y, size),
+            biome: biome.to_string(),
+            light: light_level(grid, &cell, clock),
+            time: clock.time_of_day(),
+        })
+    }
+
+    // reads nothing but its arguments, so a generated world and a seeded rng
+    // always lead to the same spawn; enemies are the cells the living ones are in
+    pub fn pick(
+        &self,
+        grid: &WorldGrid,
+        tile_types: &TileCollection,
+        clock: &WorldClock,
+        player: TilePos,
+        enemies: &[TilePos],
+        rng: &mut impl Rng,
+    ) -> Option<Spawn> {
+        for _ in 0..self.tries {
+            let Some(spot) = self.pick_spot(grid, tile_types, clock, player, rng) else {
+                continue;
+            };
+            let area = self.area(&spot.cell);
+            let population = enemies
+                .iter()
+                .filter(|enemy| self.area(enemy) == area)
+                .count();
+            if population >= self.area_cap {
+                continue;
+            }
+            let allowed: Vec<&SpawnRule> = self
+                .enemies
+                .iter()
+                .filter(|rule| rule.allows(&spot))
+                .collect();
+            let Ok(rule) = allowed.choose_weighted(rng, |rule| rule.weight) else {
+                continue;
+            };
+            return Some(Spawn {
+                enemy: rule.enemy,
+                cell: spot.cell,
+                strength: rule.strength,
+            });
+        }
+        None
+    }
+}
+
+#[derive(Resource)]
+pub struct EnemySpawner {
+    rules: SpawnRules,
+    timer: Timer,
+}
+
+impl EnemySpawner {
+    pub fn new(rules: SpawnRules) -> Self {
+        Self {
+            timer: Timer::from_seconds(rules.interval, TimerMode::Repeating),
+            rules,
+        }
+    }
+}
+
+impl Default for EnemySpawner {
+    fn default() -> Self {
+        Self::new(
+            SpawnRules::from_ron(include_str!("../assets/data/spawns.ron"))
+                .expect("invalid spawns.ron"),
+        )
+    }
+}
+
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+pub fn spawn_enemies(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    time: Res<Time>,
+    settings: Res<WorldSettings>,
+    clock: Res<WorldClock>,
+    tile_types: Res<TileCollection>,
+    mut spawner: ResMut<EnemySpawner>,
+    tilemap_q: Query<(&TileStorage, &Transform), With<WithColliders>>,
+    tile_q: Query<&TileTextureIndex>,
+    player_q: Query<&Transform, With<Player>>,
+    enemy_q: Query<&Transform, (With<Health>, Without<Player>)>,
+) {
+    if !spawner.timer.tick(time.delta()).just_finished() {
+        return;
+    }
+    let (Ok((tile_storage, map_transform)), Ok(player_transform)) =
+        (tilemap_q.get_single(), player_q.get_single())
+    else {
+        return;
+    };
+    let map_offset = map_transform.translation.truncate();
+    let cell_of = |transform: &Transform| {
+        TilePos::from_world_pos(
+            &(transform.translation.truncate() - map_offset),
+            &tile_storage.size,
+            &GRID_SIZE,
+            &MAP_TYPE,
+        )
+    };
+    let Some(player_cell) = cell_of(player_transform) else {
+        return;
+    };
+    let enemies: Vec<TilePos> = enemy_q.iter().filter_map(cell_of).collect();
+
+    // a spot is at most reach tiles away, its light comes from the sky above it
+    let reach = spawner.rules.reach;
+    let grid = WorldGrid::from_storage(
+        tile_storage,
+        &tile_q,
+        player_cell.x.saturating_sub(reach)..player_cell.x + reach + 1,
+        player_cell.y.saturating_sub(reach + 1),
+    );
+    let Some(spawn) = spawner.rules.pick(
+        &grid,
+        &tile_types,
+        &clock,
+        player_cell,
+        &enemies,
+        &mut thread_rng(),
+    ) else {
+        return;
+    };
+    let position = spawn.cell.center_in_world(&GRID_SIZE, &MAP_TYPE) + map_offset;
+    spawn.enemy.spawn(
+        &mut commands,
+        &asset_server,
+        position,
+        spawn.strength * settings.difficulty.enemy_strength(),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::map::{MAP_HEIGHT, SMALL_MAP_WIDTH};
+    use crate::map::generate_world_grid;
+
+    // the strength tells which of the rules was picked
+    fn rules() -> SpawnRules {
+        SpawnRules::from_ron(
+            r#"(
+                interval: 1.0,
+                tries: 50,
+                visible: (10, 6),
+                reach: 40,
+                area_size: 16,
+                area_cap: 2,
+                enemies: [
+                    (enemy: Slime, weight: 1, strength: 1.0, layers: [Surface], time: Some(Day)),
+                    (enemy: Slime, weight: 1, strength: 2.0, layers: [Surface], time: Some(Night)),
+                    (enemy: Slime, weight: 1, strength: 3.0, layers: [Underground]),
+                    (enemy: Slime, weight: 1, strength: 4.0, layers: [Caverns]),
+                ],
+            )"#,
+        )
+        .unwrap()
+    }
+
+    fn world() -> WorldGrid {
+        generate_world_grid(
+            &mut StdRng::seed_from_u64(42),
+            TilemapSize {
+                x: SMALL_MAP_WIDTH,
+                y: MAP_HEIGHT,
+            },
+        )
+    }
+
+    // standing on the ground in the middle of the map
+    fn player_cell(grid: &WorldGrid) -> TilePos {
+        let x = grid.size().x / 2;
+        TilePos {
+            x,
+            y: grid.sky_row(x),
+        }
+    }
+
+    fn picks(rules: &SpawnRules, clock: &WorldClock, enemies: &[TilePos]) -> Vec<Option<Spawn>> {
+        let grid = world();
+        let tile_types = TileCollection::new();
+        let player = player_cell(&grid);
+        (0..100)
+            .map(|seed| {
+                rules.pick(
+                    &grid,
+                    &tile_types,
+                    clock,
+                    player,
+                    enemies,
+                    &mut StdRng::seed_from_u64(seed),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn pick_is_reproducible() {
+        let rules = rules();
+        let clock = WorldClock::at(0.5);
+        let first = picks(&rules, &clock, &[]);
+        assert_eq!(first, picks(&rules, &clock, &[]));
+        assert!(first.iter().any(Option::is_some));
+    }
+
+    #[test]
+    fn spawns_off_screen_on_solid_ground() {
+        let rules = rules();
+        let grid = world();
+        let player = player_cell(&grid);
+        let spawns: Vec<Spawn> = picks(&rules, &WorldClock::at(0.5), &[])
+            .into_iter()
+            .flatten()
+            .collect();
+        assert!(!spawns.is_empty());
+        for spawn in spawns {
+            let cell = spawn.cell;
+            assert!(!grid.is_solid(&cell));
+            assert!(grid.is_solid(&TilePos {
+                x: cell.x,
+                y: cell.y - 1
+            }));
+            let (dx, dy) = (cell.x.abs_diff(player.x), cell.y.abs_diff(player.y));
+            assert!(dx > rules.visible.0 || dy > rules.visible.1);
+            assert!(dx <= rules.reach && dy <= rules.reach);
+        }
+    }
+
+    #[test]
+    fn full_areas_get_no_enemies() {
+        let rules = rules();
+        let size = world().size();
+        let corners: Vec<TilePos> = (0..size.x)
+            .step_by(rules.area_size as usize)
+            .flat_map(|x| {
+                (0..size.y)
+                    .step_by(rules.area_size as usize)
+                    .map(move |y| TilePos { x, y })
+            })
+            .collect();
+        let crowd = |per_area: usize| -> Vec<TilePos> {
+            corners
+                .iter()
+                .flat_map(|corner| vec![*corner; per_area])
+                .collect()
+        };
+
+        let clock = WorldClock::at(0.5);
+        let full = picks(&rules, &clock, &crowd(rules.area_cap));
+        assert!(full.iter().all(Option::is_none));
+        let almost_full = picks(&rules, &clock, &crowd(rules.area_cap - 1));
+        assert!(almost_full.iter().any(Option::is_some));
+    }
+
+    #[test]
+    fn rule_follows_layer_and_time() {
+        let rules = rules();
+        let size = world().size();
+        for (clock, surface_strength) in [(WorldClock::at(0.5), 1.), (WorldClock::at(0.), 2.)] {
+            for spawn in picks(&rules, &clock, &[]).into_iter().flatten() {
+                let expected = match DepthLayer::of(spawn.cell.y, size) {
+                    DepthLayer::Surface => surface_strength,
+                    DepthLayer::Underground => 3.,
+                    DepthLayer::Caverns => 4.,
+                };
+                assert_eq!(spawn.strength, expected);
+            }
+        }
+    }
+}
@@ -0,0 +1,173 @@
+use std::collections::HashMap;
+
+use bevy::{prelude::*, sprite::Anchor};
+use bevy_ecs_tilemap::prelude::*;
+
+use crate::constants::{
+    depth::Z_STATIONS,
+    map::{map_transform_vec2, GRID_SIZE, MAP_TYPE, TILE_SIZE},
+    stations::*,
+};
+use crate::game_state::WorldEntity;
+use crate::item::ItemId;
+use crate::player::Player;
+use crate::world_settings::WorldSettings;
+
+// object standing in the foreground map, its cells are kept in the StationMap
+#[derive(Component)]
+pub struct CraftingStation {
+    item: ItemId,
+    // bottom left cell
+    origin: TilePos,
+    size: UVec2,
+}
+
+// sent when a station is taken down, its item drops where the station stood
+pub struct StationDestroyed {
+    pub item: ItemId,
+    pub origin: TilePos,
+}
+
+// cells taken by the crafting stations, they stay out of the TileStorage of the foreground,
+// so the colliders, the explosions and the save of the tiles never run into them
+#[derive(Resource, Default)]
+pub struct StationMap(HashMap<TilePos, Entity>);
+
+// texture atlas made of stations_strip.png
+#[derive(Resource)]
+pub struct StationSprites(pub Handle<TextureAtlas>);
+
+// crafting stations close enough to the player to be used
+#[derive(Resource, Default)]
+pub struct NearbyStations(pub Vec<ItemId>);
+
+impl CraftingStation {
+    pub fn item(&self) -> &ItemId {
+        &self.item
+    }
+
+    pub fn origin(&self) -> TilePos {
+        self.origin
+    }
+
+    pub fn cells(&self) -> impl Iterator<Item = TilePos> + '_ {
+        (0..self.size.x).flat_map(move |dx| {
+            (0..self.size.y).map(move |dy| TilePos {
+                x: self.origin.x + dx,
+                y: self.origin.y + dy,
+            })
+        })
+    }
+}
+
+impl StationMap {
+    pub fn get(&self, tile_pos: &TilePos) -> Option<Entity> {
+        self.0.get(tile_pos).copied()
+    }
+}
+
+// all cells of a station have to lay inside of the map
+pub fn station_fits(origin: TilePos, size: UVec2, map_size: &TilemapSize) -> bool {
+    origin.x + size.x <= map_size.x && origin.y + size.y <= map_size.y
+}
+
+pub fn load_station_sprites(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut texture_atlases: ResMut<Assets<TextureAtlas>>,
+) {
+    let texture_handle: Handle<Image> = asset_server.load("stations_strip.png");
+    let atlas = TextureAtlas::from_grid(
+        texture_handle,
+        Vec2::splat(STATION_SPRITE_SIZE),
+        STATION_SPRITES,
+        1,
+        None,
+        None,
+    );
+    commands.insert_resource(StationSprites(texture_atlases.add(atlas)));
+}
+
+pub fn spawn_station(
+    commands: &mut Commands,
+    station_sprites: &StationSprites,
+    station_map: &mut StationMap,
+    map_size: &TilemapSize,
+    item: ItemId,
+    sprite: usize,
+    origin: TilePos,
+    size: UVec2,
+) -> Entity {
+    // sprites are drawn from the bottom left corner of the station
+    let corner = origin.center_in_world(&GRID_SIZE, &MAP_TYPE) + map_transform_vec2(map_size)
+        - Vec2::new(TILE_SIZE.x, TILE_SIZE.y) / 2.;
+
+    let station = CraftingStation { item, origin, size };
+    let cells: Vec<TilePos> = station.cells().collect();
+    let station_entity = commands
+        .spawn(SpriteSheetBundle {
+            sprite: TextureAtlasSprite {
+                index: sprite,
+                anchor: Anchor::BottomLeft,
+                ..default()
+            },
+            texture_atlas: station_sprites.0.clone(),
+            transform: Transform::from_translation(corner.extend(Z_STATIONS)),
+            ..default()
+        })
+        .insert(Name::new(format!("Station({})", station.item.0)))
+        .insert(WorldEntity)
+        .insert(station)
+        .id();
+
+    for cell in cells {
+        station_map.0.insert(cell, station_entity);
+    }
+    station_entity
+}
+
+// frees every cell of the station at once
+pub fn remove_station(
+    commands: &mut Commands,
+    station_map: &mut StationMap,
+    station_entity: Entity,
+    station: &CraftingStation,
+    destroyed_stations: &mut EventWriter<StationDestroyed>,
+) {
+    commands.entity(station_entity).despawn_recursive();
+    for cell in station.cells() {
+        station_map.0.remove(&cell);
+    }
+    destroyed_stations.send(StationDestroyed {
+        item: station.item.clone(),
+        origin: station.origin,
+    });
+}
+
+pub fn find_nearby_stations(
+    player_q: Query<&Transform, With<Player>>,
+    station_q: Query<&CraftingStation>,
+    mut nearby: ResMut<NearbyStations>,
+    settings: Res<WorldSettings>,
+) {
+    let player_pos = player_q.single().translation.truncate() - settings.map_transform();
+    let grid = Vec2::new(GRID_SIZE.x, GRID_SIZE.y);
+
+    let mut found: Vec<ItemId> = Vec::new();
+    for station in station_q.iter() {
+        // distance to the closest point of the station
+        let min = Vec2::new(station.origin.x as f32, station.origin.y as f32) * grid - grid / 2.;
+        let max = min + station.size.as_vec2() * grid;
+        let closest = player_pos.clamp(min, max);
+        if player_pos.distance(closest) <= STATION_RANGE * TILE_SIZE.x
+            && !found.contains(&station.item)
+        {
+            found.push(station.item.clone());
+        }
+    }
+
+    // the crafting panel is rebuilt only when the list changes
+    if nearby.0 != found {
+        nearby.0 = found;
+    }
+}
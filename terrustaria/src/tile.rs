@@ -1,56 +1,140 @@
+use bevy::prelude::*;
 use bevy_ecs_tilemap::prelude::*;
 
-use crate::constants::{map::MAP_SIZE, offsets::*};
+use crate::constants::offsets::*;
+use crate::item::ItemId;
 
 pub struct TileType {
-    #[allow(dead_code)]
     name: String,
     rarity: f32,
+    // damage needed to break the tile
+    hardness: f32,
+    // mining power a pickaxe needs to break the tile at all
+    min_power: u32,
     texture_index: TileTextureIndex,
-    valid: Box<dyn Fn(u32, u32) -> bool>,
+    // item dropped when the tile is mined
+    drop: Option<ItemId>,
+    valid: Box<dyn Fn(u32, u32) -> bool + Send + Sync>,
 }
 
 impl TileType {
     fn new(
         name: String,
         rarity: f32,
+        hardness: f32,
+        min_power: u32,
         tile_offset: u32,
-        valid: impl Fn(u32, u32) -> bool + 'static,
+        drop: Option<&str>,
+        valid: impl Fn(u32, u32) -> bool + Send + Sync + 'static,
     ) -> Self {
         Self {
             name,
             rarity,
+            hardness,
+            min_power,
             texture_index: TileTextureIndex(tile_offset * 5),
+            drop: drop.map(ItemId::new),
             valid: Box::new(valid),
         }
     }
 
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
     pub fn get_rarity(&self) -> f32 {
         self.rarity
     }
 
+    pub fn get_hardness(&self) -> f32 {
+        self.hardness
+    }
+
+    pub fn get_min_power(&self) -> u32 {
+        self.min_power
+    }
+
     pub fn get_texture_index(&self) -> TileTextureIndex {
         self.texture_index
     }
 
-    pub fn is_valid(&self, pos: &TilePos) -> bool {
-        (self.valid)(pos.x, pos.y) && pos.x < MAP_SIZE.x && pos.y < MAP_SIZE.y
+    pub fn get_drop(&self) -> Option<&ItemId> {
+        self.drop.as_ref()
+    }
+
+    pub fn is_valid(&self, pos: &TilePos, map_size: &TilemapSize) -> bool {
+        (self.valid)(pos.x, pos.y) && pos.x < map_size.x && pos.y < map_size.y
+    }
+}
+
+// background tile of the wall map, it cannot be mined
+pub struct WallType {
+    // stable key the wall is saved under
+    name: String,
+    texture_index: TileTextureIndex,
+}
+
+impl WallType {
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn get_texture_index(&self) -> TileTextureIndex {
+        self.texture_index
     }
 }
 
+#[derive(Resource)]
 pub struct TileCollection {
     types: Vec<TileType>,
+    walls: Vec<WallType>,
 }
 
 impl TileCollection {
     pub fn new() -> Self {
         Self {
             types: vec![
-                TileType::new(String::from("Dirt"), 60.0, DIRT_OFFSET, |_, _| true),
-                TileType::new(String::from("Stone"), 10.0, STONE_OFFSET, |_, y| y < 12),
-                TileType::new(String::from("Water"), 10.0, WATER_OFFSET, |_, y| y < 20),
-                TileType::new(String::from("Diamond"), 1., DIAMOND_OFFSET, |_, y| y < 12),
+                TileType::new(
+                    String::from("Dirt"),
+                    60.0,
+                    4.,
+                    0,
+                    DIRT_OFFSET,
+                    Some("dirt"),
+                    |_, _| true,
+                ),
+                TileType::new(
+                    String::from("Stone"),
+                    10.0,
+                    8.,
+                    35,
+                    STONE_OFFSET,
+                    Some("stone"),
+                    |_, y| y < 12,
+                ),
+                TileType::new(
+                    String::from("Water"),
+                    10.0,
+                    2.,
+                    0,
+                    WATER_OFFSET,
+                    None,
+                    |_, y| y < 20,
+                ),
+                TileType::new(
+                    String::from("Diamond"),
+                    1.,
+                    12.,
+                    55,
+                    DIAMOND_OFFSET,
+                    Some("diamond"),
+                    |_, y| y < 12,
+                ),
             ],
+            walls: vec![WallType {
+                name: String::from("DirtWall"),
+                texture_index: TileTextureIndex(DIRT_WALL_INDEX),
+            }],
         }
     }
 
@@ -62,17 +146,47 @@ impl TileCollection {
         &self.types[idx]
     }
 
+    pub fn by_texture_index(&self, texture_index: TileTextureIndex) -> Option<&TileType> {
+        self.types
+            .iter()
+            .find(|tile_type| tile_type.texture_index.0 == texture_index.0)
+    }
+
+    pub fn by_name(&self, name: &str) -> Option<&TileType> {
+        self.types.iter().find(|tile_type| tile_type.name == name)
+    }
+
     pub fn stone_tile(&self) -> &TileType {
         self.at(1)
     }
 
-    pub fn rarity_sum_valid(&self, pos: &TilePos) -> f32 {
+    pub fn wall_by_texture_index(&self, texture_index: TileTextureIndex) -> Option<&WallType> {
+        self.walls
+            .iter()
+            .find(|wall_type| wall_type.texture_index.0 == texture_index.0)
+    }
+
+    pub fn wall_by_name(&self, name: &str) -> Option<&WallType> {
+        self.walls.iter().find(|wall_type| wall_type.name == name)
+    }
+
+    pub fn dirt_wall(&self) -> &WallType {
+        &self.walls[0]
+    }
+
+    pub fn rarity_sum_valid(&self, pos: &TilePos, map_size: &TilemapSize) -> f32 {
         let mut sum: f32 = 0.0;
         for tile_type in &self.types {
-            if tile_type.is_valid(pos) {
+            if tile_type.is_valid(pos, map_size) {
                 sum += tile_type.get_rarity();
             }
         }
         sum
     }
 }
+
+impl Default for TileCollection {
+    fn default() -> Self {
+        Self::new()
+    }
+}
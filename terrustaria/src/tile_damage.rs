@@ -0,0 +1,107 @@
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+use bevy_ecs_tilemap::prelude::*;
+
+use crate::constants::mining::*;
+use crate::map::DamageMap;
+use crate::tile::TileCollection;
+
+// damage dealt to a foreground tile which is not broken yet
+#[derive(Component)]
+pub struct TileDamage {
+    amount: f32,
+    // seconds since the tile was hit for the last time
+    idle: f32,
+}
+
+// tile of the damage map, drawn over a damaged foreground tile
+#[derive(Component)]
+pub struct DamageOverlay;
+
+impl TileDamage {
+    pub fn new(amount: f32) -> Self {
+        Self { amount, idle: 0. }
+    }
+
+    pub fn get_amount(&self) -> f32 {
+        self.amount
+    }
+
+    // 0 for an intact tile, DAMAGE_STAGES for a tile just about to break
+    pub fn stage(&self, hardness: f32) -> u32 {
+        let stage = (self.amount / hardness * DAMAGE_STAGES as f32).ceil() as u32;
+        stage.min(DAMAGE_STAGES)
+    }
+}
+
+pub fn heal_damaged_tiles(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut damaged_q: Query<(Entity, &mut TileDamage)>,
+) {
+    for (tile_entity, mut damage) in damaged_q.iter_mut() {
+        damage.idle += time.delta_seconds();
+        if damage.idle < HEAL_DELAY {
+            continue;
+        }
+
+        damage.amount -= HEAL_RATE * time.delta_seconds();
+        if damage.amount <= 0. {
+            commands.entity(tile_entity).remove::<TileDamage>();
+        }
+    }
+}
+
+// keeps a cracked copy of every damaged tile in the damage map,
+// the cracked frames follow the intact one in tiles_strip.png
+pub fn update_damage_overlay(
+    mut commands: Commands,
+    tile_types: Res<TileCollection>,
+    damaged_q: Query<(&TilePos, &TileTextureIndex, &TileDamage), Without<DamageOverlay>>,
+    mut overlay_q: Query<(Entity, &TilePos, &mut TileTextureIndex), With<DamageOverlay>>,
+    mut damage_map_q: Query<(Entity, &mut TileStorage), With<DamageMap>>,
+) {
+    let (damage_map, mut tile_storage) = damage_map_q.single_mut();
+
+    let mut wanted: HashMap<TilePos, TileTextureIndex> = HashMap::new();
+    for (tile_pos, texture_index, damage) in damaged_q.iter() {
+        if let Some(tile_type) = tile_types.by_texture_index(*texture_index) {
+            let stage = damage.stage(tile_type.get_hardness());
+            if stage > 0 {
+                wanted.insert(*tile_pos, TileTextureIndex(texture_index.0 + stage));
+            }
+        }
+    }
+
+    for (overlay_entity, tile_pos, mut texture_index) in overlay_q.iter_mut() {
+        match wanted.remove(tile_pos) {
+            Some(new_index) => {
+                if texture_index.0 != new_index.0 {
+                    *texture_index = new_index;
+                }
+            }
+            None => {
+                commands.entity(overlay_entity).despawn_recursive();
+                tile_storage.remove(tile_pos);
+            }
+        }
+    }
+
+    for (tile_pos, texture_index) in wanted {
+        let overlay_entity = commands
+            .spawn(TileBundle {
+                position: tile_pos,
+                tilemap_id: TilemapId(damage_map),
+                texture_index,
+                ..Default::default()
+            })
+            .insert(Name::new(format!(
+                "DamageTile({},{})",
+                tile_pos.x, tile_pos.y
+            )))
+            .insert(DamageOverlay)
+            .id();
+        tile_storage.set(&tile_pos, overlay_entity);
+    }
+}
@@ -0,0 +1,132 @@
+use bevy::prelude::*;
+use bevy_ecs_tilemap::prelude::TilemapSize;
+use rand::{rngs::StdRng, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+use crate::constants::{death::*, enemies::*, items::DROP_LIFETIME, map::*};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum WorldSize {
+    Small,
+    #[default]
+    Medium,
+    Large,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Difficulty {
+    Easy,
+    #[default]
+    Normal,
+    Hard,
+}
+
+// chosen when the world is created and never changed afterwards
+#[derive(Resource, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WorldSettings {
+    // the same seed always generates the same world
+    pub seed: u64,
+    pub size: WorldSize,
+    pub difficulty: Difficulty,
+    // seconds before a drop nobody picked up disappears, death piles have their own
+    #[serde(default = "default_drop_lifetime")]
+    pub drop_lifetime: u32,
+}
+
+fn default_drop_lifetime() -> u32 {
+    DROP_LIFETIME
+}
+
+impl Default for WorldSettings {
+    fn default() -> Self {
+        Self {
+            seed: 0,
+            size: WorldSize::default(),
+            difficulty: Difficulty::default(),
+            drop_lifetime: default_drop_lifetime(),
+        }
+    }
+}
+
+impl WorldSize {
+    pub fn width(self) -> u32 {
+        match self {
+            WorldSize::Small => SMALL_MAP_WIDTH,
+            WorldSize::Medium => MEDIUM_MAP_WIDTH,
+            WorldSize::Large => LARGE_MAP_WIDTH,
+        }
+    }
+
+    // the presets are cycled through by a button
+    pub fn next(self) -> Self {
+        match self {
+            WorldSize::Small => WorldSize::Medium,
+            WorldSize::Medium => WorldSize::Large,
+            WorldSize::Large => WorldSize::Small,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            WorldSize::Small => "Small",
+            WorldSize::Medium => "Medium",
+            WorldSize::Large => "Large",
+        }
+    }
+}
+
+impl Difficulty {
+    pub fn next(self) -> Self {
+        match self {
+            Difficulty::Easy => Difficulty::Normal,
+            Difficulty::Normal => Difficulty::Hard,
+            Difficulty::Hard => Difficulty::Easy,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Difficulty::Easy => "Easy",
+            Difficulty::Normal => "Normal",
+            Difficulty::Hard => "Hard",
+        }
+    }
+
+    pub fn death_drop_share(self) -> f32 {
+        match self {
+            Difficulty::Easy => EASY_DROP_SHARE,
+            Difficulty::Normal => NORMAL_DROP_SHARE,
+            Difficulty::Hard => HARD_DROP_SHARE,
+        }
+    }
+
+    pub fn enemy_strength(self) -> f32 {
+        match self {
+            Difficulty::Easy => EASY_ENEMY_STRENGTH,
+            Difficulty::Normal => NORMAL_ENEMY_STRENGTH,
+            Difficulty::Hard => HARD_ENEMY_STRENGTH,
+        }
+    }
+
+    // the character is deleted when it dies
+    pub fn permanent_death(self) -> bool {
+        self == Difficulty::Hard
+    }
+}
+
+impl WorldSettings {
+    pub fn map_size(&self) -> TilemapSize {
+        TilemapSize {
+            x: self.size.width(),
+            y: MAP_HEIGHT,
+        }
+    }
+
+    pub fn map_transform(&self) -> Vec2 {
+        map_transform_vec2(&self.map_size())
+    }
+
+    pub fn rng(&self) -> StdRng {
+        StdRng::seed_from_u64(self.seed)
+    }
+}